@@ -14,7 +14,8 @@ def parse_port_mapping(port_mapping: str) -> PortMap:
 
     Args:
         port_mapping: String in format 'container_port[/protocol]:host_port'
-                      e.g., '8080/tcp:8080' or '3000:3000'
+                      e.g., '8080/tcp:8080' or '3000:3000'. An empty host port
+                      ('8080:') publishes to a random free host port.
 
     Returns:
         A PortMap with from and to fields set.
@@ -43,7 +44,7 @@ def build_run_command(
         run_params.tag = tag
     if port_mapping is not None:
         port_map = parse_port_mapping(port_mapping)
-        run_params.container_host_config.CopyFrom(ContainerHostConfig(port_map=port_map))
+        run_params.container_host_config.CopyFrom(ContainerHostConfig(port_maps=[port_map]))
     return RemoteCommand(run=run_params)
 
 
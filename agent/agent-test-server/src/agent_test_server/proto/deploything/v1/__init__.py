@@ -7,9 +7,15 @@ from agent_test_server.proto.deploything.v1.remote_command_pb2 import (
 )
 from agent_test_server.proto.deploything.v1.agent_snapshot_pb2 import (
     AgentSnapshot,
+    ContainerHealth,
     ContainerState,
     ContainerStatus,
 )
+from agent_test_server.proto.deploything.v1.agent_message_pb2 import (
+    AgentMessage,
+    BuildLog,
+    BuildResult,
+)
 
 __all__ = [
     "ContainerHostConfig",
@@ -18,6 +24,10 @@ __all__ = [
     "RunParams",
     "StopParams",
     "AgentSnapshot",
+    "ContainerHealth",
     "ContainerState",
     "ContainerStatus",
+    "AgentMessage",
+    "BuildLog",
+    "BuildResult",
 ]
@@ -12,7 +12,7 @@ from agent_test_server.commands.builders import (
     build_stop_command,
     serialize_command,
 )
-from agent_test_server.proto.deploything.v1 import AgentSnapshot
+from agent_test_server.proto.deploything.v1 import AgentMessage, AgentSnapshot
 
 
 # Type alias for snapshot callback
@@ -69,13 +69,16 @@ class AgentConnection:
         await self._websocket.send(data)
 
     async def _run(self) -> None:
-        """Internal method to receive messages and invoke snapshot callback."""
+        """Internal method to receive agent messages and invoke snapshot callback."""
         try:
             async for message in self._websocket:
                 if isinstance(message, bytes):
-                    snapshot = AgentSnapshot()
-                    snapshot.ParseFromString(message)
-                    if self._snapshot_callback is not None:
-                        await self._snapshot_callback(snapshot)
+                    agent_message = AgentMessage()
+                    agent_message.ParseFromString(message)
+                    if (
+                        agent_message.WhichOneof("message") == "snapshot"
+                        and self._snapshot_callback is not None
+                    ):
+                        await self._snapshot_callback(agent_message.snapshot)
         finally:
             self._closed = True
@@ -6,6 +6,7 @@ from datetime import datetime, timezone
 
 from agent_test_server.proto.deploything.v1 import (
     AgentSnapshot,
+    ContainerHealth,
     ContainerState,
     ContainerStatus,
 )
@@ -17,6 +18,11 @@ def _state_name(state: ContainerState) -> str:
         ContainerState.CONTAINER_STATE_UNSPECIFIED: "UNSPECIFIED",
         ContainerState.CONTAINER_STATE_RUNNING: "RUNNING",
         ContainerState.CONTAINER_STATE_EXITED: "EXITED",
+        ContainerState.CONTAINER_STATE_CREATED: "CREATED",
+        ContainerState.CONTAINER_STATE_PAUSED: "PAUSED",
+        ContainerState.CONTAINER_STATE_RESTARTING: "RESTARTING",
+        ContainerState.CONTAINER_STATE_REMOVING: "REMOVING",
+        ContainerState.CONTAINER_STATE_DEAD: "DEAD",
     }
     return mapping.get(state, "UNKNOWN")
 
@@ -35,8 +41,32 @@ def _format_container(container: ContainerStatus, indent: str = "  ") -> str:
     lines = []
     lines.append(f"{indent}- id: {container.id or '(none)'}")
     lines.append(f"{indent}  name: {container.name or '(none)'}")
-    lines.append(f"{indent}  image: {container.image_id or '(none)'}")
+    if container.HasField("managed") and not container.managed:
+        lines.append(f"{indent}  unmanaged: true")
+    if container.HasField("app_name"):
+        lines.append(f"{indent}  app: {container.app_name}")
+    if container.HasField("deployment_id"):
+        lines.append(f"{indent}  deployment: {container.deployment_id}")
+    lines.append(f"{indent}  image: {container.image or container.image_id or '(none)'}")
     lines.append(f"{indent}  state: {_state_name(container.container_state)}")
+    if container.HasField("status_text"):
+        lines.append(f"{indent}  status: {container.status_text}")
+    for port in container.ports:
+        lines.append(
+            f"{indent}  port: {port.host_ip}:{port.host_port} -> {port.container_port}"
+        )
+    if container.HasField("exit_code"):
+        lines.append(f"{indent}  exit code: {container.exit_code}")
+    if container.HasField("restart_count"):
+        lines.append(f"{indent}  restarts: {container.restart_count}")
+    if container.HasField("stats"):
+        stats = container.stats
+        lines.append(
+            f"{indent}  cpu: {stats.cpu_percent:.1f}%"
+            f"  mem: {stats.memory_usage_bytes}/{stats.memory_limit_bytes} bytes"
+        )
+    if container.HasField("health"):
+        lines.append(f"{indent}  health: {ContainerHealth.Name(container.health)}")
     return "\n".join(lines)
 
 
@@ -51,6 +81,12 @@ def format_snapshot(snapshot: AgentSnapshot) -> str:
     """
     lines = ["AgentSnapshot:"]
     lines.append(f"  timestamp: {_format_timestamp(snapshot)}")
+    if snapshot.HasField("host"):
+        host = snapshot.host
+        lines.append(
+            f"  host: {host.hostname} ({host.operating_system}, {host.cpu_count} CPUs,"
+            f" {host.memory_available_bytes}/{host.memory_total_bytes} bytes free)"
+        )
 
     if snapshot.container_status:
         lines.append("  containers:")
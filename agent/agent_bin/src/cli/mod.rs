@@ -1,5 +1,9 @@
+use std::path::PathBuf;
+
 use clap::{Parser, Subcommand};
 
+use crate::docker_api::{parse_bind_prefix, parse_max_cpus, parse_maximum, parse_stop_signal};
+
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 pub struct AgentCli {
@@ -19,12 +23,59 @@ pub enum Commands {
         #[arg(short = 'p', long = "control-plane-port", default_value_t = 4040)]
         control_plane_port: u16,
 
-        /// The interval, in seconds, at which the agent will send snapshots to the control plane.
-        #[arg(short = 'i', long = "snapshot-interval", default_value_t = 10)]
+        /// The interval, in seconds, at which the agent will send full snapshots to the control
+        /// plane. Deltas are also sent as soon as containers change.
+        #[arg(short = 'i', long = "snapshot-interval", default_value_t = 60)]
         snapshot_interval_secs: u16,
 
         /// The port on which the agent should listen for incoming requests.
         #[arg(long = "proxy-port", default_value_t = 3000)]
         proxy_port: u16,
+
+        /// The maximum memory limit, in bytes, that may be given to a container.
+        /// Containers that do not request a limit are given this one.
+        #[arg(long = "max-memory-bytes", value_parser = parse_maximum)]
+        max_memory_bytes: Option<i64>,
+
+        /// The maximum relative CPU weight that may be given to a container.
+        #[arg(long = "max-cpu-shares", value_parser = parse_maximum)]
+        max_cpu_shares: Option<i64>,
+
+        /// The maximum number of CPUs a container may use.
+        /// Containers that do not request a CPU quota are given this one.
+        #[arg(long = "max-cpus", value_parser = parse_max_cpus)]
+        max_cpus: Option<f64>,
+
+        /// The maximum number of processes a container may run.
+        /// Containers that do not request a pids limit are given this one.
+        #[arg(long = "max-pids", value_parser = parse_maximum)]
+        max_pids: Option<i64>,
+
+        /// Also report containers that were not created by the agent in snapshots.
+        #[arg(long = "include-unmanaged")]
+        include_unmanaged: bool,
+
+        /// A program that the control plane may run inside managed containers, matched
+        /// exactly against the command's first argument. May be given more than once.
+        /// Exec is disabled unless at least one program is allowed.
+        #[arg(long = "exec-allow", value_name = "PROGRAM")]
+        exec_allow: Vec<String>,
+
+        /// A host directory that containers may bind-mount, along with anything beneath it.
+        /// The directory must exist, and symlinks in bind mounts are followed before they
+        /// are checked. May be given more than once. Bind mounts are disabled unless at
+        /// least one directory is allowed.
+        #[arg(long = "bind-allow", value_name = "PATH", value_parser = parse_bind_prefix)]
+        bind_allow: Vec<PathBuf>,
+
+        /// The time, in seconds, a container is given to exit after being asked to stop
+        /// before it is killed, unless the stop command specifies its own.
+        #[arg(long = "stop-timeout", default_value_t = 10)]
+        stop_timeout_secs: u16,
+
+        /// The signal that asks a container to stop, unless the stop command specifies its
+        /// own. Defaults to the image's STOPSIGNAL, or SIGTERM.
+        #[arg(long = "stop-signal", value_parser = parse_stop_signal)]
+        stop_signal: Option<String>,
     },
 }
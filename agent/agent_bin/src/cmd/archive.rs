@@ -0,0 +1,368 @@
+use std::time::{Duration, Instant};
+
+use agent_wire::deploything::v1::{
+    ArchiveChunk, ArchiveDownloadEnded, ArchiveUploadResult, UploadArchiveChunk,
+    UploadArchiveParams,
+};
+use bollard::Docker;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use tokio::{sync::mpsc::Sender, task::JoinHandle};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, instrument, warn};
+
+use crate::{
+    docker_api::{self, DockerApiError},
+    ws::encode_message,
+};
+
+/// Upper bound on the size of an archive that is buffered while its chunks arrive.
+pub const MAX_UPLOAD_BYTES: usize = 64 * 1024 * 1024;
+
+/// The most uploads whose archive may be arriving at once.
+pub const MAX_PENDING_UPLOADS: usize = 8;
+
+/// How long an upload may go without receiving a chunk before it is dropped.
+pub const PENDING_UPLOAD_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// The most downloads that may be streaming at once. Each holds a Docker archive stream
+/// and a task until the archive has been sent.
+pub const MAX_DOWNLOADS: usize = 4;
+
+/// The most archive data sent in a single `ArchiveChunk`.
+pub const DOWNLOAD_CHUNK_BYTES: usize = 256 * 1024;
+
+/// Why a chunk was not appended to an upload.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ChunkError {
+    /// The chunk was damaged in transit and may be sent again.
+    #[error("chunk checksum {actual:08x} does not match {expected:08x}")]
+    Corrupt { actual: u32, expected: u32 },
+
+    #[error("archive exceeds {MAX_UPLOAD_BYTES} bytes")]
+    TooLarge,
+}
+
+/// An archive that is still being uploaded by the control plane.
+#[derive(Debug)]
+pub struct PendingUpload {
+    params: UploadArchiveParams,
+    archive: Vec<u8>,
+    last_received: Instant,
+}
+
+impl PendingUpload {
+    pub fn new(params: UploadArchiveParams) -> Self {
+        Self {
+            params,
+            archive: Vec::new(),
+            last_received: Instant::now(),
+        }
+    }
+
+    pub fn params(&self) -> &UploadArchiveParams {
+        &self.params
+    }
+
+    /// Whether the control plane has stopped sending the archive.
+    pub fn is_expired(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.last_received) >= PENDING_UPLOAD_TTL
+    }
+
+    /// Appends a chunk of the archive, returning the total number of bytes received.
+    /// A chunk that does not match its checksum is not appended.
+    pub fn append(&mut self, chunk: &UploadArchiveChunk) -> Result<usize, ChunkError> {
+        let data = chunk.data();
+
+        let crc32 = crc32fast::hash(data);
+        if crc32 != chunk.crc32() {
+            return Err(ChunkError::Corrupt {
+                actual: crc32,
+                expected: chunk.crc32(),
+            });
+        }
+
+        if self.archive.len() + data.len() > MAX_UPLOAD_BYTES {
+            return Err(ChunkError::TooLarge);
+        }
+
+        self.archive.extend_from_slice(data);
+        self.last_received = Instant::now();
+        Ok(self.archive.len())
+    }
+
+    /// Returns the complete archive, once its checksum has been verified.
+    pub fn finish(self) -> Result<Bytes, String> {
+        if let Some(expected) = self.params.crc32 {
+            let crc32 = crc32fast::hash(&self.archive);
+            if crc32 != expected {
+                return Err(format!(
+                    "archive checksum {crc32:08x} does not match {expected:08x}"
+                ));
+            }
+        }
+
+        Ok(Bytes::from(self.archive))
+    }
+}
+
+/// Extracts a received archive in the background, then sends an `ArchiveUploadResult` on
+/// `msg_tx`, so that a large archive does not hold up other commands.
+pub fn spawn_upload(
+    upload_id: String,
+    params: UploadArchiveParams,
+    archive: Bytes,
+    docker: Docker,
+    msg_tx: Sender<Message>,
+) {
+    tokio::task::spawn(upload(upload_id, params, archive, docker, msg_tx));
+}
+
+#[instrument(skip(params, archive, docker, msg_tx), fields(container_id = params.container_id(), path = params.path()))]
+async fn upload(
+    upload_id: String,
+    params: UploadArchiveParams,
+    archive: Bytes,
+    docker: Docker,
+    msg_tx: Sender<Message>,
+) {
+    let uploaded =
+        docker_api::upload_archive(&docker, params.container_id(), params.path(), archive).await;
+
+    let result = ArchiveUploadResult {
+        upload_id: Some(upload_id),
+        error: uploaded.err().map(|e| e.to_string()),
+    };
+
+    if msg_tx.send(encode_message(result)).await.is_err() {
+        error!("Failed to send archive upload result");
+    }
+}
+
+/// Sends a downloaded archive to the control plane in the background.
+///
+/// Chunks of at most `DOWNLOAD_CHUNK_BYTES` are sent on the low-priority `bulk_tx` channel.
+/// The final `ArchiveDownloadEnded` is sent on `msg_tx`.
+pub fn spawn_download(
+    download_id: String,
+    archive: impl Stream<Item = Result<Bytes, DockerApiError>> + Send + 'static,
+    bulk_tx: Sender<Message>,
+    msg_tx: Sender<Message>,
+) -> JoinHandle<()> {
+    tokio::task::spawn(download(download_id, archive, bulk_tx, msg_tx))
+}
+
+#[instrument(skip(archive, bulk_tx, msg_tx))]
+async fn download(
+    download_id: String,
+    archive: impl Stream<Item = Result<Bytes, DockerApiError>>,
+    bulk_tx: Sender<Message>,
+    msg_tx: Sender<Message>,
+) {
+    let mut archive = std::pin::pin!(archive);
+    let mut hasher = crc32fast::Hasher::new();
+    let mut offset = 0;
+    let mut error = None;
+
+    while let Some(data) = archive.next().await {
+        let data = match data {
+            Ok(data) => data,
+            Err(e) => {
+                error = Some(e.to_string());
+                break;
+            }
+        };
+
+        for piece in data.chunks(DOWNLOAD_CHUNK_BYTES) {
+            hasher.update(piece);
+
+            let chunk = ArchiveChunk {
+                download_id: Some(download_id.clone()),
+                offset: Some(offset),
+                data: Some(piece.to_vec()),
+                crc32: Some(crc32fast::hash(piece)),
+            };
+            offset += piece.len() as u64;
+
+            if bulk_tx.send(encode_message(chunk)).await.is_err() {
+                warn!("Bulk message channel closed, abandoning download");
+                return;
+            }
+        }
+    }
+
+    let ended = ArchiveDownloadEnded {
+        download_id: Some(download_id),
+        total_bytes: Some(offset),
+        crc32: Some(hasher.finalize()),
+        error,
+    };
+
+    if msg_tx.send(encode_message(ended)).await.is_err() {
+        error!("Failed to send archive download end");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use agent_wire::deploything::v1::{
+        AgentMessage, ArchiveChunk, ArchiveDownloadEnded, UploadArchiveChunk, UploadArchiveParams,
+        agent_message,
+    };
+    use bytes::Bytes;
+    use prost::Message as _;
+    use tokio::sync::mpsc::{self, Receiver};
+    use tokio_tungstenite::tungstenite::Message;
+
+    use crate::{
+        cmd::archive::{
+            ChunkError, DOWNLOAD_CHUNK_BYTES, MAX_UPLOAD_BYTES, PENDING_UPLOAD_TTL, PendingUpload,
+            download,
+        },
+        docker_api::DockerApiError,
+    };
+
+    fn chunk(data: &[u8]) -> UploadArchiveChunk {
+        UploadArchiveChunk {
+            upload_id: Some("upload".to_string()),
+            data: Some(data.to_vec()),
+            crc32: Some(crc32fast::hash(data)),
+            last: None,
+        }
+    }
+
+    #[test]
+    fn upload_verifies_chunk_and_archive_checksums() {
+        let mut upload = PendingUpload::new(UploadArchiveParams {
+            crc32: Some(crc32fast::hash(b"hello world")),
+            ..Default::default()
+        });
+
+        assert_eq!(upload.append(&chunk(b"hello ")), Ok(6));
+
+        let mut corrupt = chunk(b"world");
+        corrupt.data = Some(b"w0rld".to_vec());
+        assert!(upload.append(&corrupt).is_err());
+
+        assert_eq!(upload.append(&chunk(b"world")), Ok(11));
+        assert_eq!(upload.finish().unwrap().as_ref(), b"hello world");
+    }
+
+    #[test]
+    fn upload_rejects_mismatched_archive() {
+        let mut upload = PendingUpload::new(UploadArchiveParams {
+            crc32: Some(crc32fast::hash(b"something else")),
+            ..Default::default()
+        });
+
+        upload.append(&chunk(b"hello world")).unwrap();
+        assert!(upload.finish().is_err());
+    }
+
+    #[test]
+    fn upload_rejects_archive_over_limit() {
+        let mut upload = PendingUpload::new(UploadArchiveParams::default());
+        upload.append(&chunk(b"abc")).unwrap();
+
+        // The zeroed buffer is not touched, so it costs no memory until it is copied.
+        let rest = vec![0; MAX_UPLOAD_BYTES - 2];
+        let mut oversized = chunk(b"");
+        oversized.crc32 = Some(crc32fast::hash(&rest));
+        oversized.data = Some(rest);
+
+        assert_eq!(upload.append(&oversized), Err(ChunkError::TooLarge));
+        assert_eq!(upload.append(&chunk(b"d")), Ok(4));
+    }
+
+    #[test]
+    fn upload_expires_once_idle_for_ttl() {
+        let upload = PendingUpload::new(UploadArchiveParams::default());
+        let now = Instant::now();
+
+        assert!(!upload.is_expired(now));
+        assert!(upload.is_expired(now + PENDING_UPLOAD_TTL));
+    }
+
+    fn decode(rx: &mut Receiver<Message>) -> Vec<agent_message::Message> {
+        let mut messages = Vec::new();
+        while let Ok(message) = rx.try_recv() {
+            let message = AgentMessage::decode(message.into_data()).unwrap();
+            messages.push(message.message.unwrap());
+        }
+        messages
+    }
+
+    #[tokio::test]
+    async fn download_splits_archive_into_checksummed_chunks() {
+        let first = vec![1; DOWNLOAD_CHUNK_BYTES + 100];
+        let second = vec![2; 10];
+        let archive = futures_util::stream::iter([
+            Ok(Bytes::from(first.clone())),
+            Ok(Bytes::from(second.clone())),
+        ]);
+
+        let (bulk_tx, mut bulk_rx) = mpsc::channel(16);
+        let (msg_tx, mut msg_rx) = mpsc::channel(16);
+        download("download".to_string(), archive, bulk_tx, msg_tx).await;
+
+        let chunks: Vec<ArchiveChunk> = decode(&mut bulk_rx)
+            .into_iter()
+            .map(|message| match message {
+                agent_message::Message::ArchiveChunk(chunk) => chunk,
+                other => panic!("unexpected message {other:?}"),
+            })
+            .collect();
+
+        let expected = [
+            (0, &first[..DOWNLOAD_CHUNK_BYTES]),
+            (DOWNLOAD_CHUNK_BYTES, &first[DOWNLOAD_CHUNK_BYTES..]),
+            (first.len(), &second[..]),
+        ];
+        assert_eq!(chunks.len(), expected.len());
+
+        for (chunk, (offset, data)) in chunks.iter().zip(expected) {
+            assert_eq!(chunk.download_id(), "download");
+            assert_eq!(chunk.offset(), offset as u64);
+            assert_eq!(chunk.data(), data);
+            assert_eq!(chunk.crc32(), crc32fast::hash(data));
+        }
+
+        let whole = [first, second].concat();
+        assert_eq!(
+            decode(&mut msg_rx),
+            vec![agent_message::Message::ArchiveDownloadEnded(
+                ArchiveDownloadEnded {
+                    download_id: Some("download".to_string()),
+                    total_bytes: Some(whole.len() as u64),
+                    crc32: Some(crc32fast::hash(&whole)),
+                    error: None,
+                }
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn download_reports_error_after_partial_archive() {
+        let archive = futures_util::stream::iter([
+            Ok(Bytes::from_static(b"partial")),
+            Err(DockerApiError::ArchiveDownloadFailed {
+                container_id: "abc123".to_string(),
+                path: "/data".to_string(),
+            }),
+        ]);
+
+        let (bulk_tx, mut bulk_rx) = mpsc::channel(16);
+        let (msg_tx, mut msg_rx) = mpsc::channel(16);
+        download("download".to_string(), archive, bulk_tx, msg_tx).await;
+
+        assert_eq!(decode(&mut bulk_rx).len(), 1);
+
+        let [agent_message::Message::ArchiveDownloadEnded(ended)] = &decode(&mut msg_rx)[..] else {
+            panic!("expected a single ArchiveDownloadEnded");
+        };
+        assert_eq!(ended.total_bytes, Some(7));
+        assert!(ended.error.is_some());
+    }
+}
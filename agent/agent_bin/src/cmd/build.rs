@@ -0,0 +1,178 @@
+use std::time::{Duration, Instant};
+
+use agent_wire::deploything::v1::{BuildLog, BuildParams, BuildResult};
+use bollard::Docker;
+use tokio::sync::mpsc::{self, Sender};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, instrument, warn};
+
+use crate::{docker_api, ws::encode_message};
+
+/// Upper bound on the size of a build context that is buffered while its chunks arrive.
+/// Contexts are held in memory, so together with `MAX_PENDING_BUILDS` this bounds how
+/// much memory the control plane can make the agent hold.
+pub const MAX_BUILD_CONTEXT_BYTES: usize = 64 * 1024 * 1024;
+
+/// The most builds whose context may be uploading at once.
+pub const MAX_PENDING_BUILDS: usize = 4;
+
+/// How long a build may go without receiving a chunk of its context before it is dropped.
+pub const PENDING_BUILD_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// A build whose context is still being uploaded by the control plane.
+#[derive(Debug)]
+pub struct PendingBuild {
+    params: BuildParams,
+    context: Vec<u8>,
+    max_context_bytes: usize,
+    last_received: Instant,
+}
+
+impl PendingBuild {
+    pub fn new(params: BuildParams) -> Result<Self, &'static str> {
+        if params.build_id().is_empty() {
+            return Err("build ID is empty");
+        }
+
+        if params.image_name().is_empty() {
+            return Err("image name is empty");
+        }
+
+        Ok(Self {
+            params,
+            context: Vec::new(),
+            max_context_bytes: MAX_BUILD_CONTEXT_BYTES,
+            last_received: Instant::now(),
+        })
+    }
+
+    /// Whether the control plane has stopped sending the build's context.
+    pub fn is_expired(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.last_received) >= PENDING_BUILD_TTL
+    }
+
+    /// Appends a chunk of the build context, returning the total number of bytes received.
+    pub fn append(&mut self, data: &[u8]) -> Result<usize, String> {
+        if self.context.len() + data.len() > self.max_context_bytes {
+            return Err(format!(
+                "build context exceeds {} bytes",
+                self.max_context_bytes
+            ));
+        }
+
+        self.context.extend_from_slice(data);
+        self.last_received = Instant::now();
+        Ok(self.context.len())
+    }
+
+    /// Runs the build in the background, streaming `BuildLog`s followed by a single
+    /// `BuildResult` to the control plane.
+    pub fn spawn(self, docker: Docker, msg_tx: Sender<Message>) {
+        tokio::task::spawn(self.run(docker, msg_tx));
+    }
+
+    #[instrument(skip_all, fields(build_id = self.params.build_id()))]
+    async fn run(self, docker: Docker, msg_tx: Sender<Message>) {
+        let build_id = self.params.build_id().to_string();
+        let (log_tx, mut log_rx) = mpsc::channel::<String>(64);
+
+        let forward_logs = {
+            let msg_tx = msg_tx.clone();
+            let build_id = build_id.clone();
+            async move {
+                while let Some(line) = log_rx.recv().await {
+                    let log = BuildLog {
+                        build_id: Some(build_id.clone()),
+                        line: Some(line),
+                    };
+                    if msg_tx.send(encode_message(log)).await.is_err() {
+                        warn!("Message channel closed, dropping build logs");
+                        break;
+                    }
+                }
+            }
+        };
+
+        let build = docker_api::build_image(&docker, &self.params, self.context.into(), log_tx);
+        let (result, _) = tokio::join!(build, forward_logs);
+
+        let result = BuildResult {
+            build_id: Some(build_id),
+            image_name: self.params.image_name.clone(),
+            tag: self.params.tag.clone(),
+            error: result.err().map(|e| e.to_string()),
+        };
+
+        if msg_tx.send(encode_message(result)).await.is_err() {
+            error!("Failed to send build result");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use agent_wire::deploything::v1::BuildParams;
+
+    use crate::cmd::build::{PENDING_BUILD_TTL, PendingBuild};
+
+    fn params() -> BuildParams {
+        BuildParams {
+            build_id: Some("build".to_string()),
+            image_name: Some("app".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn append_accumulates_context() {
+        let mut build = PendingBuild::new(params()).unwrap();
+
+        assert_eq!(build.append(b"hello "), Ok(6));
+        assert_eq!(build.append(b""), Ok(6));
+        assert_eq!(build.append(b"world"), Ok(11));
+        assert_eq!(build.context, b"hello world");
+    }
+
+    #[test]
+    fn append_rejects_context_over_limit() {
+        let mut build = PendingBuild::new(params()).unwrap();
+        build.max_context_bytes = 4;
+        build.append(b"abc").unwrap();
+
+        assert_eq!(
+            build.append(b"de"),
+            Err("build context exceeds 4 bytes".to_string())
+        );
+
+        // The rejected chunk is not appended.
+        assert_eq!(build.append(b"d"), Ok(4));
+    }
+
+    #[test]
+    fn new_requires_build_id_and_image_name() {
+        let mut missing_id = params();
+        missing_id.build_id = Some(String::new());
+        assert_eq!(
+            PendingBuild::new(missing_id).unwrap_err(),
+            "build ID is empty"
+        );
+
+        let mut missing_image = params();
+        missing_image.image_name = None;
+        assert_eq!(
+            PendingBuild::new(missing_image).unwrap_err(),
+            "image name is empty"
+        );
+    }
+
+    #[test]
+    fn expires_once_idle_for_ttl() {
+        let build = PendingBuild::new(params()).unwrap();
+        let now = Instant::now();
+
+        assert!(!build.is_expired(now));
+        assert!(build.is_expired(now + PENDING_BUILD_TTL));
+    }
+}
@@ -0,0 +1,185 @@
+use std::pin::Pin;
+
+use agent_wire::deploything::v1::{ExecOutput, ExecResult, LogStream};
+use bollard::Docker;
+use futures_util::StreamExt;
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::mpsc::{self, Receiver, Sender},
+    task::JoinHandle,
+};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, instrument, warn};
+
+use crate::{
+    docker_api::{self, Exec},
+    ws::encode_message,
+};
+
+/// The most output captured from each stream of a non-interactive exec.
+pub const MAX_CAPTURED_BYTES: usize = 1024 * 1024;
+
+/// An exec whose process has been started.
+#[derive(Debug)]
+pub struct RunningExec {
+    task: JoinHandle<()>,
+    stdin: Option<Sender<Vec<u8>>>,
+}
+
+impl RunningExec {
+    /// Waits for the process in the background, then sends an `ExecResult` on `msg_tx`.
+    ///
+    /// An interactive exec's output is forwarded as `ExecOutput` frames on the low-priority
+    /// `bulk_tx` channel, and its standard input is accepted through `write_stdin`. Otherwise,
+    /// output is captured into the result.
+    pub fn spawn(
+        exec_id: String,
+        exec: Exec,
+        interactive: bool,
+        docker: Docker,
+        bulk_tx: Sender<Message>,
+        msg_tx: Sender<Message>,
+    ) -> Self {
+        let (stdin, stdin_rx) = if interactive {
+            let (tx, rx) = mpsc::channel(16);
+            (Some(tx), Some(rx))
+        } else {
+            (None, None)
+        };
+
+        let task = tokio::task::spawn(run(exec_id, exec, stdin_rx, docker, bulk_tx, msg_tx));
+
+        Self { task, stdin }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Queues `data` for the process's standard input, closing it afterwards if `close` is
+    /// set. Fails rather than waiting if the process is not keeping up with its input.
+    pub fn write_stdin(&mut self, data: Vec<u8>, close: bool) -> Result<(), &'static str> {
+        let Some(stdin) = &self.stdin else {
+            return Err("exec is not interactive or its standard input is closed");
+        };
+
+        if !data.is_empty() {
+            stdin
+                .try_send(data)
+                .map_err(|_| "standard input is not being read")?;
+        }
+
+        if close {
+            self.stdin = None;
+        }
+
+        Ok(())
+    }
+}
+
+#[instrument(skip(exec, stdin_rx, docker, bulk_tx, msg_tx))]
+async fn run(
+    exec_id: String,
+    exec: Exec,
+    stdin_rx: Option<Receiver<Vec<u8>>>,
+    docker: Docker,
+    bulk_tx: Sender<Message>,
+    msg_tx: Sender<Message>,
+) {
+    let Exec {
+        id: docker_exec_id,
+        mut output,
+        input,
+    } = exec;
+
+    let interactive = stdin_rx.is_some();
+    let writer = stdin_rx.map(|stdin_rx| tokio::task::spawn(write_stdin(input, stdin_rx)));
+
+    let mut captured = CapturedOutput::default();
+    let mut error = None;
+
+    while let Some(chunk) = output.next().await {
+        match chunk {
+            Ok((stream, data)) if interactive => {
+                let frame = ExecOutput {
+                    exec_id: Some(exec_id.clone()),
+                    stream: Some(stream as i32),
+                    data: Some(data.to_vec()),
+                };
+
+                if bulk_tx.send(encode_message(frame)).await.is_err() {
+                    warn!("Bulk message channel closed, dropping exec output");
+                    break;
+                }
+            }
+            Ok((stream, data)) => captured.push(stream, &data),
+            Err(e) => {
+                error = Some(e.to_string());
+                break;
+            }
+        }
+    }
+
+    if let Some(writer) = writer {
+        writer.abort();
+    }
+
+    let mut exit_code = None;
+    if error.is_none() {
+        match docker_api::exec_exit_code(&docker, &docker_exec_id).await {
+            Ok(code) => exit_code = code,
+            Err(e) => error = Some(e.to_string()),
+        }
+    }
+
+    let result = ExecResult {
+        exec_id: Some(exec_id),
+        exit_code,
+        stdout: (!interactive).then_some(captured.stdout),
+        stderr: (!interactive).then_some(captured.stderr),
+        output_truncated: Some(captured.truncated),
+        error,
+    };
+
+    if msg_tx.send(encode_message(result)).await.is_err() {
+        error!("Failed to send exec result");
+    }
+}
+
+/// Copies queued input to the process until the sender is dropped, then closes its
+/// standard input.
+async fn write_stdin(mut input: Pin<Box<dyn AsyncWrite + Send>>, mut rx: Receiver<Vec<u8>>) {
+    while let Some(data) = rx.recv().await {
+        if let Err(e) = input.write_all(&data).await {
+            warn!("Failed to write exec input: {e}");
+            return;
+        }
+    }
+
+    if let Err(e) = input.shutdown().await {
+        warn!("Failed to close exec input: {e}");
+    }
+}
+
+#[derive(Debug, Default)]
+struct CapturedOutput {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    truncated: bool,
+}
+
+impl CapturedOutput {
+    fn push(&mut self, stream: LogStream, data: &[u8]) {
+        let buffer = match stream {
+            LogStream::Stderr => &mut self.stderr,
+            _ => &mut self.stdout,
+        };
+
+        let room = MAX_CAPTURED_BYTES.saturating_sub(buffer.len());
+        if data.len() > room {
+            self.truncated = true;
+        }
+
+        buffer.extend_from_slice(&data[..data.len().min(room)]);
+    }
+}
@@ -1,58 +1,214 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Instant};
 
-use agent_wire::deploything::v1::{RunParams, StopParams, remote_command::Command};
+use agent_wire::deploything::v1::{
+    BuildContextChunk, BuildParams, CreateNetworkParams, DesiredState, DownloadArchiveParams,
+    ExecInput, ExecParams, ListVolumesParams, PauseParams, RemoveNetworkParams, RemoveParams,
+    RemoveVolumeParams, RequestSnapshotParams, RestartParams, RunParams, StopParams,
+    SubscribeLogsParams, UnpauseParams, UnsubscribeLogsParams, UploadArchiveChunk,
+    UploadArchiveParams, remote_command::Command,
+};
 use bollard::Docker;
-use tokio::sync::mpsc::Receiver;
+use tokio::{
+    sync::{
+        mpsc::{Receiver, Sender},
+        watch,
+    },
+    task::JoinHandle,
+    time::{Duration, MissedTickBehavior},
+};
+use tokio_tungstenite::tungstenite::Message;
 use tracing::{instrument, warn};
 
 use crate::{
-    cmd::{CommandBundle, CommandResponse},
-    docker_api::Container,
+    cmd::{
+        CommandBundle, CommandResponse,
+        archive::{self, ChunkError, PendingUpload},
+        build::{self, PendingBuild},
+        exec::RunningExec,
+        logs,
+    },
+    docker_api::{
+        self, BindAllowlist, Container, DockerApiError, ExecAllowlist, ResourceMaximums,
+        StopOptions,
+    },
+    reconcile,
+    snapshot::SnapshotTrigger,
 };
 
+/// How often transfers that have stopped receiving chunks are looked for.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Limits and defaults the agent applies to commands from the control plane.
+#[derive(Debug, Default, Clone)]
+pub struct CommandPolicy {
+    pub maximums: ResourceMaximums,
+    pub exec_allowlist: ExecAllowlist,
+    pub bind_allowlist: BindAllowlist,
+    /// Used for stops that do not specify their own timeout or signal, and for restarts.
+    pub stop: StopOptions,
+}
+
+impl CommandPolicy {
+    /// Checks a container's mounts and applies its resource limits before it is created.
+    pub fn apply(&self, params: &mut RunParams) -> Result<(), DockerApiError> {
+        let host_config = params.container_host_config.get_or_insert_default();
+
+        self.bind_allowlist.resolve(&mut host_config.mounts)?;
+        host_config.resources = Some(self.maximums.enforce(host_config.resources.as_ref())?);
+
+        Ok(())
+    }
+}
+
 pub struct CommandHandler<'d> {
     cmd_rx: Receiver<CommandBundle>,
+    msg_tx: Sender<Message>,
+    /// Low-priority messages, such as log frames, that must not hold up `msg_tx`.
+    bulk_tx: Sender<Message>,
     docker: &'d Docker,
+    policy: CommandPolicy,
+    snapshots: SnapshotTrigger,
+    /// Hands the most recent desired state to the `Reconciler`.
+    desired_tx: watch::Sender<Option<DesiredState>>,
     containers: HashMap<String, Container<'d>>,
+    builds: HashMap<String, PendingBuild>,
+    log_subscriptions: HashMap<String, JoinHandle<()>>,
+    execs: HashMap<String, RunningExec>,
+    uploads: HashMap<String, PendingUpload>,
+    downloads: HashMap<String, JoinHandle<()>>,
 }
 
 impl<'d> CommandHandler<'d> {
-    pub fn new(docker: &'d Docker, cmd_rx: Receiver<CommandBundle>) -> Self {
+    pub fn new(
+        docker: &'d Docker,
+        cmd_rx: Receiver<CommandBundle>,
+        msg_tx: Sender<Message>,
+        bulk_tx: Sender<Message>,
+        policy: CommandPolicy,
+        snapshots: SnapshotTrigger,
+        desired_tx: watch::Sender<Option<DesiredState>>,
+    ) -> Self {
         let containers = HashMap::new();
+        let builds = HashMap::new();
+        let log_subscriptions = HashMap::new();
+        let execs = HashMap::new();
+        let uploads = HashMap::new();
+        let downloads = HashMap::new();
         Self {
             cmd_rx,
+            msg_tx,
+            bulk_tx,
             docker,
+            policy,
+            snapshots,
+            desired_tx,
             containers,
+            builds,
+            log_subscriptions,
+            execs,
+            uploads,
+            downloads,
         }
     }
 
     #[instrument(skip(self))]
     pub async fn handle_incoming(&mut self) {
-        while let Some(cmd_bundle) = self.cmd_rx.recv().await {
-            let response = match cmd_bundle.command() {
-                Command::Run(params) => self.handle_run_command(params).await,
-                Command::Stop(params) => self.handle_stop_command(params).await,
-            };
+        let mut sweep = tokio::time::interval(SWEEP_INTERVAL);
+        sweep.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                cmd_bundle = self.cmd_rx.recv() => {
+                    let Some(cmd_bundle) = cmd_bundle else {
+                        break;
+                    };
+                    self.handle_command(cmd_bundle).await;
+                }
+                _ = sweep.tick() => self.sweep_pending(),
+            }
+        }
+    }
+
+    async fn handle_command(&mut self, cmd_bundle: CommandBundle) {
+        // These arrive in quick succession and never change containers.
+        let affects_snapshot = !matches!(
+            cmd_bundle.command(),
+            Command::ExecInput(_) | Command::UploadArchiveChunk(_)
+        );
+
+        let response = match cmd_bundle.command() {
+            Command::Run(params) => self.handle_run_command(params).await,
+            Command::Stop(params) => self.handle_stop_command(params).await,
+            Command::Remove(params) => self.handle_remove_command(params).await,
+            Command::Restart(params) => self.handle_restart_command(params).await,
+            Command::Pause(params) => self.handle_pause_command(params).await,
+            Command::Unpause(params) => self.handle_unpause_command(params).await,
+            Command::Build(params) => self.handle_build_command(params),
+            Command::BuildContext(chunk) => self.handle_build_context_command(chunk),
+            Command::ListVolumes(params) => self.handle_list_volumes_command(params).await,
+            Command::RemoveVolume(params) => self.handle_remove_volume_command(params).await,
+            Command::CreateNetwork(params) => self.handle_create_network_command(params).await,
+            Command::RemoveNetwork(params) => self.handle_remove_network_command(params).await,
+            Command::RequestSnapshot(params) => self.handle_request_snapshot_command(params),
+            Command::SubscribeLogs(params) => self.handle_subscribe_logs_command(params).await,
+            Command::UnsubscribeLogs(params) => self.handle_unsubscribe_logs_command(params),
+            Command::Exec(params) => self.handle_exec_command(params).await,
+            Command::ExecInput(input) => self.handle_exec_input_command(input),
+            Command::UploadArchive(params) => self.handle_upload_archive_command(params).await,
+            Command::UploadArchiveChunk(chunk) => self.handle_upload_archive_chunk_command(chunk),
+            Command::DownloadArchive(params) => self.handle_download_archive_command(params).await,
+            Command::SetDesiredState(desired) => self.handle_set_desired_state_command(desired),
+        };
 
-            cmd_bundle.reply(response);
+        cmd_bundle.reply(response);
+        if affects_snapshot {
+            self.snapshots.request();
         }
     }
 
+    /// Drops transfers that the control plane has abandoned part way through.
+    fn sweep_pending(&mut self) {
+        let now = Instant::now();
+
+        self.builds.retain(|build_id, build| {
+            let expired = build.is_expired(now);
+            if expired {
+                warn!("Dropping build {build_id}, its context stopped arriving");
+            }
+            !expired
+        });
+
+        self.uploads.retain(|upload_id, upload| {
+            let expired = upload.is_expired(now);
+            if expired {
+                warn!("Dropping upload {upload_id}, its archive stopped arriving");
+            }
+            !expired
+        });
+    }
+
     #[instrument(skip(self), ret)]
     async fn handle_run_command(&mut self, params: &RunParams) -> CommandResponse {
-        let container = Container::spawn_from_image(
-            &self.docker,
-            &params.image_name(),
-            &params.tag(),
-            params.container_host_config.as_ref(),
-        );
+        let mut params = params.clone();
+
+        if let Err(e) = self.policy.apply(&mut params) {
+            return CommandResponse::Error {
+                message: format!("Failed to start container: {e}"),
+            };
+        }
+
+        let container = Container::spawn_from_image(self.docker, &params);
 
         match container.await {
             Ok(container) => {
                 // FIXME: why do we need to allocate so many of the same strings here?
                 let container_id = container.id().to_string();
+                let ports = container.ports().to_vec();
                 self.containers.insert(container_id.clone(), container);
-                CommandResponse::ContainerStarted { container_id }
+                CommandResponse::ContainerStarted {
+                    container_id,
+                    ports,
+                }
             }
             Err(e) => CommandResponse::Error {
                 message: format!("Failed to start container: {e}"),
@@ -74,7 +230,21 @@ impl<'d> CommandHandler<'d> {
             };
         };
 
-        match container.stop().await {
+        let options = self
+            .policy
+            .stop
+            .with_overrides(params.timeout.as_ref(), params.signal.as_deref());
+
+        let options = match options {
+            Ok(options) => options,
+            Err(e) => {
+                return CommandResponse::Error {
+                    message: format!("Failed to stop container: {e}"),
+                };
+            }
+        };
+
+        match container.stop(&options).await {
             Ok(_) => {
                 let container_id = params.container_id().to_string();
                 self.containers.remove(params.container_id());
@@ -85,4 +255,507 @@ impl<'d> CommandHandler<'d> {
             },
         }
     }
+
+    #[instrument(skip(self), ret)]
+    async fn handle_remove_command(&mut self, params: &RemoveParams) -> CommandResponse {
+        let container_id = params.container_id();
+
+        let removed = docker_api::remove_container(
+            self.docker,
+            container_id,
+            params.remove_volumes(),
+            params.force(),
+        );
+
+        match removed.await {
+            Ok(_) => {
+                self.containers.remove(container_id);
+                CommandResponse::ContainerRemoved {
+                    container_id: container_id.to_string(),
+                }
+            }
+            Err(e) => CommandResponse::Error {
+                message: format!("Failed to remove container: {e}"),
+            },
+        }
+    }
+
+    #[instrument(skip(self), ret)]
+    async fn handle_restart_command(&mut self, params: &RestartParams) -> CommandResponse {
+        match docker_api::restart_container(self.docker, params.container_id(), &self.policy.stop)
+            .await
+        {
+            Ok(_) => CommandResponse::ContainerRestarted {
+                container_id: params.container_id().to_string(),
+            },
+            Err(e) => CommandResponse::Error {
+                message: format!("Failed to restart container: {e}"),
+            },
+        }
+    }
+
+    #[instrument(skip(self), ret)]
+    async fn handle_pause_command(&mut self, params: &PauseParams) -> CommandResponse {
+        match docker_api::pause_container(self.docker, params.container_id()).await {
+            Ok(_) => CommandResponse::ContainerPaused {
+                container_id: params.container_id().to_string(),
+            },
+            Err(e) => CommandResponse::Error {
+                message: format!("Failed to pause container: {e}"),
+            },
+        }
+    }
+
+    #[instrument(skip(self), ret)]
+    async fn handle_unpause_command(&mut self, params: &UnpauseParams) -> CommandResponse {
+        match docker_api::unpause_container(self.docker, params.container_id()).await {
+            Ok(_) => CommandResponse::ContainerUnpaused {
+                container_id: params.container_id().to_string(),
+            },
+            Err(e) => CommandResponse::Error {
+                message: format!("Failed to unpause container: {e}"),
+            },
+        }
+    }
+
+    #[instrument(skip(self), ret)]
+    fn handle_build_command(&mut self, params: &BuildParams) -> CommandResponse {
+        let build_id = params.build_id();
+
+        if self.builds.contains_key(build_id) {
+            warn!("Received duplicate build command for {build_id}");
+            return CommandResponse::Error {
+                message: format!("Build already in progress: {build_id}"),
+            };
+        }
+
+        if self.builds.len() >= build::MAX_PENDING_BUILDS {
+            return CommandResponse::Error {
+                message: format!(
+                    "Too many builds in progress, the limit is {}",
+                    build::MAX_PENDING_BUILDS
+                ),
+            };
+        }
+
+        let build = match PendingBuild::new(params.clone()) {
+            Ok(build) => build,
+            Err(message) => {
+                return CommandResponse::Error {
+                    message: format!("Invalid build: {message}"),
+                };
+            }
+        };
+
+        self.builds.insert(build_id.to_string(), build);
+
+        CommandResponse::BuildPending {
+            build_id: build_id.to_string(),
+        }
+    }
+
+    // The chunk's data is skipped, since it can be several megabytes of binary.
+    #[instrument(skip(self, chunk), fields(build_id = chunk.build_id(), last = chunk.last()), ret)]
+    fn handle_build_context_command(&mut self, chunk: &BuildContextChunk) -> CommandResponse {
+        let build_id = chunk.build_id();
+
+        let Some(build) = self.builds.get_mut(build_id) else {
+            warn!("Received build context for unknown build {build_id}");
+            return CommandResponse::Error {
+                message: format!("Unknown build: {build_id}"),
+            };
+        };
+
+        let received_bytes = match build.append(chunk.data()) {
+            Ok(received_bytes) => received_bytes,
+            Err(message) => {
+                self.builds.remove(build_id);
+                return CommandResponse::Error {
+                    message: format!("Failed to receive build context: {message}"),
+                };
+            }
+        };
+
+        if !chunk.last() {
+            return CommandResponse::BuildContextReceived {
+                build_id: build_id.to_string(),
+                received_bytes,
+            };
+        }
+
+        if let Some(build) = self.builds.remove(build_id) {
+            build.spawn(self.docker.clone(), self.msg_tx.clone());
+        }
+
+        CommandResponse::BuildStarted {
+            build_id: build_id.to_string(),
+        }
+    }
+
+    #[instrument(skip(self), ret)]
+    async fn handle_list_volumes_command(
+        &mut self,
+        _params: &ListVolumesParams,
+    ) -> CommandResponse {
+        match docker_api::list_volumes(self.docker).await {
+            Ok(volumes) => CommandResponse::VolumeList { volumes },
+            Err(e) => CommandResponse::Error {
+                message: format!("Failed to list volumes: {e}"),
+            },
+        }
+    }
+
+    #[instrument(skip(self), ret)]
+    async fn handle_remove_volume_command(
+        &mut self,
+        params: &RemoveVolumeParams,
+    ) -> CommandResponse {
+        match docker_api::remove_volume(self.docker, params.name(), params.force()).await {
+            Ok(_) => CommandResponse::VolumeRemoved {
+                name: params.name().to_string(),
+            },
+            Err(e) => CommandResponse::Error {
+                message: format!("Failed to remove volume: {e}"),
+            },
+        }
+    }
+
+    #[instrument(skip(self), ret)]
+    async fn handle_create_network_command(
+        &mut self,
+        params: &CreateNetworkParams,
+    ) -> CommandResponse {
+        match docker_api::create_network(self.docker, params.name(), params.internal()).await {
+            Ok(network_id) => CommandResponse::NetworkCreated {
+                name: params.name().to_string(),
+                network_id,
+            },
+            Err(e) => CommandResponse::Error {
+                message: format!("Failed to create network: {e}"),
+            },
+        }
+    }
+
+    #[instrument(skip(self), ret)]
+    async fn handle_remove_network_command(
+        &mut self,
+        params: &RemoveNetworkParams,
+    ) -> CommandResponse {
+        match docker_api::remove_network(self.docker, params.name()).await {
+            Ok(_) => CommandResponse::NetworkRemoved {
+                name: params.name().to_string(),
+            },
+            Err(e) => CommandResponse::Error {
+                message: format!("Failed to remove network: {e}"),
+            },
+        }
+    }
+
+    #[instrument(skip(self), ret)]
+    fn handle_request_snapshot_command(
+        &mut self,
+        params: &RequestSnapshotParams,
+    ) -> CommandResponse {
+        self.snapshots.request_full();
+        CommandResponse::SnapshotRequested
+    }
+
+    #[instrument(skip(self), ret)]
+    async fn handle_subscribe_logs_command(
+        &mut self,
+        params: &SubscribeLogsParams,
+    ) -> CommandResponse {
+        let subscription_id = params.subscription_id();
+
+        self.log_subscriptions
+            .retain(|_, forwarder| !forwarder.is_finished());
+
+        if subscription_id.is_empty() || self.log_subscriptions.contains_key(subscription_id) {
+            return CommandResponse::Error {
+                message: format!("Log subscription {subscription_id:?} is invalid or in use"),
+            };
+        }
+
+        if self.log_subscriptions.len() >= logs::MAX_LOG_SUBSCRIPTIONS {
+            return CommandResponse::Error {
+                message: format!(
+                    "Too many log subscriptions, the limit is {}",
+                    logs::MAX_LOG_SUBSCRIPTIONS
+                ),
+            };
+        }
+
+        match docker_api::container_logs(self.docker, params).await {
+            Ok(stream) => {
+                let forwarder = logs::spawn(
+                    subscription_id.to_string(),
+                    stream,
+                    self.bulk_tx.clone(),
+                    self.msg_tx.clone(),
+                );
+                self.log_subscriptions
+                    .insert(subscription_id.to_string(), forwarder);
+
+                CommandResponse::LogsSubscribed {
+                    subscription_id: subscription_id.to_string(),
+                }
+            }
+            Err(e) => CommandResponse::Error {
+                message: format!("Failed to subscribe to logs: {e}"),
+            },
+        }
+    }
+
+    #[instrument(skip(self), ret)]
+    fn handle_unsubscribe_logs_command(
+        &mut self,
+        params: &UnsubscribeLogsParams,
+    ) -> CommandResponse {
+        let subscription_id = params.subscription_id();
+
+        match self.log_subscriptions.remove(subscription_id) {
+            Some(forwarder) => {
+                forwarder.abort();
+                CommandResponse::LogsUnsubscribed {
+                    subscription_id: subscription_id.to_string(),
+                }
+            }
+            None => CommandResponse::Error {
+                message: format!("Unknown log subscription: {subscription_id}"),
+            },
+        }
+    }
+
+    #[instrument(skip(self), ret)]
+    async fn handle_exec_command(&mut self, params: &ExecParams) -> CommandResponse {
+        let exec_id = params.exec_id();
+
+        self.execs.retain(|_, exec| !exec.is_finished());
+
+        if self.execs.contains_key(exec_id) {
+            return CommandResponse::Error {
+                message: format!("Exec {exec_id:?} is already running"),
+            };
+        }
+
+        match docker_api::start_exec(self.docker, &self.policy.exec_allowlist, params).await {
+            Ok(exec) => {
+                let exec = RunningExec::spawn(
+                    exec_id.to_string(),
+                    exec,
+                    params.interactive(),
+                    self.docker.clone(),
+                    self.bulk_tx.clone(),
+                    self.msg_tx.clone(),
+                );
+                self.execs.insert(exec_id.to_string(), exec);
+
+                CommandResponse::ExecStarted {
+                    exec_id: exec_id.to_string(),
+                }
+            }
+            Err(e) => CommandResponse::Error {
+                message: format!("Failed to start exec: {e}"),
+            },
+        }
+    }
+
+    // The input's data is skipped, since it may contain anything the user types.
+    #[instrument(skip(self, input), fields(exec_id = input.exec_id(), close_stdin = input.close_stdin()), ret)]
+    fn handle_exec_input_command(&mut self, input: &ExecInput) -> CommandResponse {
+        let exec_id = input.exec_id();
+
+        let Some(exec) = self.execs.get_mut(exec_id) else {
+            return CommandResponse::Error {
+                message: format!("Unknown exec: {exec_id}"),
+            };
+        };
+
+        match exec.write_stdin(input.data().to_vec(), input.close_stdin()) {
+            Ok(()) => CommandResponse::ExecInputAccepted {
+                exec_id: exec_id.to_string(),
+            },
+            Err(message) => CommandResponse::Error {
+                message: format!("Failed to write exec input: {message}"),
+            },
+        }
+    }
+
+    #[instrument(skip(self), ret)]
+    async fn handle_upload_archive_command(
+        &mut self,
+        params: &UploadArchiveParams,
+    ) -> CommandResponse {
+        let upload_id = params.upload_id();
+
+        if upload_id.is_empty() || self.uploads.contains_key(upload_id) {
+            return CommandResponse::Error {
+                message: format!("Upload {upload_id:?} is invalid or in progress"),
+            };
+        }
+
+        if self.uploads.len() >= archive::MAX_PENDING_UPLOADS {
+            return CommandResponse::Error {
+                message: format!(
+                    "Too many uploads in progress, the limit is {}",
+                    archive::MAX_PENDING_UPLOADS
+                ),
+            };
+        }
+
+        let target =
+            docker_api::check_archive_target(self.docker, params.container_id(), params.path());
+
+        if let Err(e) = target.await {
+            return CommandResponse::Error {
+                message: format!("Failed to upload archive: {e}"),
+            };
+        }
+
+        self.uploads
+            .insert(upload_id.to_string(), PendingUpload::new(params.clone()));
+
+        CommandResponse::UploadReceived {
+            upload_id: upload_id.to_string(),
+            received_bytes: 0,
+        }
+    }
+
+    // The chunk's data is skipped, since it can be several hundred kilobytes of binary.
+    #[instrument(skip(self, chunk), fields(upload_id = chunk.upload_id(), last = chunk.last()), ret)]
+    fn handle_upload_archive_chunk_command(
+        &mut self,
+        chunk: &UploadArchiveChunk,
+    ) -> CommandResponse {
+        let upload_id = chunk.upload_id();
+
+        let Some(upload) = self.uploads.get_mut(upload_id) else {
+            warn!("Received archive chunk for unknown upload {upload_id}");
+            return CommandResponse::Error {
+                message: format!("Unknown upload: {upload_id}"),
+            };
+        };
+
+        let received_bytes = match upload.append(chunk) {
+            Ok(received_bytes) => received_bytes,
+            Err(e) => {
+                // A corrupted chunk leaves the upload in place so that it can be sent again.
+                if !matches!(e, ChunkError::Corrupt { .. }) {
+                    self.uploads.remove(upload_id);
+                }
+                return CommandResponse::Error {
+                    message: format!("Failed to receive archive: {e}"),
+                };
+            }
+        };
+
+        if !chunk.last() {
+            return CommandResponse::UploadReceived {
+                upload_id: upload_id.to_string(),
+                received_bytes,
+            };
+        }
+
+        let Some(upload) = self.uploads.remove(upload_id) else {
+            return CommandResponse::Error {
+                message: format!("Unknown upload: {upload_id}"),
+            };
+        };
+
+        let params = upload.params().clone();
+        let archive = match upload.finish() {
+            Ok(archive) => archive,
+            Err(message) => {
+                return CommandResponse::Error {
+                    message: format!("Failed to receive archive: {message}"),
+                };
+            }
+        };
+
+        archive::spawn_upload(
+            upload_id.to_string(),
+            params,
+            archive,
+            self.docker.clone(),
+            self.msg_tx.clone(),
+        );
+
+        CommandResponse::UploadStarted {
+            upload_id: upload_id.to_string(),
+        }
+    }
+
+    #[instrument(skip(self), ret)]
+    async fn handle_download_archive_command(
+        &mut self,
+        params: &DownloadArchiveParams,
+    ) -> CommandResponse {
+        let download_id = params.download_id();
+
+        self.downloads.retain(|_, download| !download.is_finished());
+
+        if download_id.is_empty() || self.downloads.contains_key(download_id) {
+            return CommandResponse::Error {
+                message: format!("Download {download_id:?} is invalid or in progress"),
+            };
+        }
+
+        if self.downloads.len() >= archive::MAX_DOWNLOADS {
+            return CommandResponse::Error {
+                message: format!(
+                    "Too many downloads in progress, the limit is {}",
+                    archive::MAX_DOWNLOADS
+                ),
+            };
+        }
+
+        let archive =
+            docker_api::download_archive(self.docker, params.container_id(), params.path());
+
+        match archive.await {
+            Ok(archive) => {
+                let download = archive::spawn_download(
+                    download_id.to_string(),
+                    archive,
+                    self.bulk_tx.clone(),
+                    self.msg_tx.clone(),
+                );
+                self.downloads.insert(download_id.to_string(), download);
+
+                CommandResponse::DownloadStarted {
+                    download_id: download_id.to_string(),
+                }
+            }
+            Err(e) => CommandResponse::Error {
+                message: format!("Failed to download archive: {e}"),
+            },
+        }
+    }
+
+    // The desired state is skipped, since it contains every app's secrets.
+    #[instrument(skip(self, desired), fields(generation = desired.generation(), apps = desired.apps.len()), ret)]
+    fn handle_set_desired_state_command(&mut self, desired: &DesiredState) -> CommandResponse {
+        let generation = desired.generation();
+
+        let current = self
+            .desired_tx
+            .borrow()
+            .as_ref()
+            .map(|current| current.generation());
+
+        if current.is_some_and(|current| current > generation) {
+            return CommandResponse::Error {
+                message: format!("Desired state generation {generation} is out of date"),
+            };
+        }
+
+        if let Err(message) = reconcile::validate(desired) {
+            return CommandResponse::Error {
+                message: format!("Invalid desired state: {message}"),
+            };
+        }
+
+        self.desired_tx.send_replace(Some(desired.clone()));
+        CommandResponse::DesiredStateAccepted { generation }
+    }
 }
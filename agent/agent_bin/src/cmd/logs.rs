@@ -0,0 +1,67 @@
+use agent_wire::deploything::v1::{LogFrame, LogStream, LogSubscriptionEnded};
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use tokio::{sync::mpsc::Sender, task::JoinHandle};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, instrument, warn};
+
+use crate::{docker_api::DockerApiError, ws::encode_message};
+
+/// The most log subscriptions that may be streaming at once. Each holds a Docker log
+/// stream and a task until the container's output ends or it is unsubscribed.
+pub const MAX_LOG_SUBSCRIPTIONS: usize = 16;
+
+/// Forwards a container's logs to the control plane in the background.
+///
+/// Frames are sent on the low-priority `bulk_tx` channel, so a chatty container is slowed
+/// down to the speed of the connection rather than delaying other messages. The final
+/// `LogSubscriptionEnded` is sent on `msg_tx`.
+pub fn spawn(
+    subscription_id: String,
+    logs: impl Stream<Item = Result<(LogStream, Bytes), DockerApiError>> + Send + 'static,
+    bulk_tx: Sender<Message>,
+    msg_tx: Sender<Message>,
+) -> JoinHandle<()> {
+    tokio::task::spawn(forward(subscription_id, logs, bulk_tx, msg_tx))
+}
+
+#[instrument(skip(logs, bulk_tx, msg_tx))]
+async fn forward(
+    subscription_id: String,
+    logs: impl Stream<Item = Result<(LogStream, Bytes), DockerApiError>>,
+    bulk_tx: Sender<Message>,
+    msg_tx: Sender<Message>,
+) {
+    let mut logs = std::pin::pin!(logs);
+    let mut error = None;
+
+    while let Some(chunk) = logs.next().await {
+        match chunk {
+            Ok((stream, data)) => {
+                let frame = LogFrame {
+                    subscription_id: Some(subscription_id.clone()),
+                    stream: Some(stream as i32),
+                    data: Some(data.to_vec()),
+                };
+
+                if bulk_tx.send(encode_message(frame)).await.is_err() {
+                    warn!("Bulk message channel closed, dropping logs");
+                    return;
+                }
+            }
+            Err(e) => {
+                error = Some(e.to_string());
+                break;
+            }
+        }
+    }
+
+    let ended = LogSubscriptionEnded {
+        subscription_id: Some(subscription_id),
+        error,
+    };
+
+    if msg_tx.send(encode_message(ended)).await.is_err() {
+        error!("Failed to send log subscription end");
+    }
+}
@@ -1,27 +1,239 @@
+mod archive;
+mod build;
+mod exec;
 mod handler;
+mod logs;
 
-use agent_wire::deploything::v1::{RemoteCommand, remote_command};
-pub use handler::CommandHandler;
+use agent_wire::deploything::v1::{
+    ArchiveDownloadStarted, ArchiveUploadAccepted, BuildAccepted, CommandError, CommandResult,
+    ContainerPaused, ContainerRemoved, ContainerRestarted, ContainerStarted, ContainerStopped,
+    ContainerUnpaused, DesiredStateAccepted, ExecInputAccepted, ExecStarted, LogsSubscribed,
+    LogsUnsubscribed, NetworkCreated, NetworkRemoved, PublishedPort, RemoteCommand,
+    SnapshotRequested, Volume, VolumeList, VolumeRemoved, command_result, remote_command,
+};
+pub use handler::{CommandHandler, CommandPolicy};
 use tokio::sync::oneshot;
 use tracing::{error, instrument};
 
 #[derive(Debug)]
 pub enum CommandResponse {
-    ContainerStarted { container_id: String },
-    ContainerStopped { container_id: String },
-    Error { message: String },
+    ContainerStarted {
+        container_id: String,
+        ports: Vec<PublishedPort>,
+    },
+    ContainerStopped {
+        container_id: String,
+    },
+    ContainerRemoved {
+        container_id: String,
+    },
+    ContainerRestarted {
+        container_id: String,
+    },
+    ContainerPaused {
+        container_id: String,
+    },
+    ContainerUnpaused {
+        container_id: String,
+    },
+    BuildPending {
+        build_id: String,
+    },
+    BuildContextReceived {
+        build_id: String,
+        received_bytes: usize,
+    },
+    BuildStarted {
+        build_id: String,
+    },
+    VolumeList {
+        volumes: Vec<Volume>,
+    },
+    VolumeRemoved {
+        name: String,
+    },
+    NetworkCreated {
+        name: String,
+        network_id: String,
+    },
+    NetworkRemoved {
+        name: String,
+    },
+    SnapshotRequested,
+    LogsSubscribed {
+        subscription_id: String,
+    },
+    LogsUnsubscribed {
+        subscription_id: String,
+    },
+    ExecStarted {
+        exec_id: String,
+    },
+    ExecInputAccepted {
+        exec_id: String,
+    },
+    UploadReceived {
+        upload_id: String,
+        received_bytes: usize,
+    },
+    UploadStarted {
+        upload_id: String,
+    },
+    DownloadStarted {
+        download_id: String,
+    },
+    DesiredStateAccepted {
+        generation: u64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl CommandResponse {
+    /// Converts the response into the `CommandResult` reported to the control plane.
+    pub fn into_result(self, command_id: Option<String>) -> CommandResult {
+        let result = match self {
+            CommandResponse::ContainerStarted {
+                container_id,
+                ports,
+            } => command_result::Result::ContainerStarted(ContainerStarted {
+                container_id: Some(container_id),
+                ports,
+            }),
+            CommandResponse::ContainerStopped { container_id } => {
+                command_result::Result::ContainerStopped(ContainerStopped {
+                    container_id: Some(container_id),
+                })
+            }
+            CommandResponse::ContainerRemoved { container_id } => {
+                command_result::Result::ContainerRemoved(ContainerRemoved {
+                    container_id: Some(container_id),
+                })
+            }
+            CommandResponse::ContainerRestarted { container_id } => {
+                command_result::Result::ContainerRestarted(ContainerRestarted {
+                    container_id: Some(container_id),
+                })
+            }
+            CommandResponse::ContainerPaused { container_id } => {
+                command_result::Result::ContainerPaused(ContainerPaused {
+                    container_id: Some(container_id),
+                })
+            }
+            CommandResponse::ContainerUnpaused { container_id } => {
+                command_result::Result::ContainerUnpaused(ContainerUnpaused {
+                    container_id: Some(container_id),
+                })
+            }
+            CommandResponse::BuildPending { build_id } => {
+                command_result::Result::BuildAccepted(BuildAccepted {
+                    build_id: Some(build_id),
+                    received_bytes: Some(0),
+                    started: Some(false),
+                })
+            }
+            CommandResponse::BuildContextReceived {
+                build_id,
+                received_bytes,
+            } => command_result::Result::BuildAccepted(BuildAccepted {
+                build_id: Some(build_id),
+                received_bytes: Some(received_bytes as u64),
+                started: Some(false),
+            }),
+            CommandResponse::BuildStarted { build_id } => {
+                command_result::Result::BuildAccepted(BuildAccepted {
+                    build_id: Some(build_id),
+                    received_bytes: None,
+                    started: Some(true),
+                })
+            }
+            CommandResponse::VolumeList { volumes } => {
+                command_result::Result::VolumeList(VolumeList { volumes })
+            }
+            CommandResponse::VolumeRemoved { name } => {
+                command_result::Result::VolumeRemoved(VolumeRemoved { name: Some(name) })
+            }
+            CommandResponse::NetworkCreated { name, network_id } => {
+                command_result::Result::NetworkCreated(NetworkCreated {
+                    name: Some(name),
+                    network_id: Some(network_id),
+                })
+            }
+            CommandResponse::NetworkRemoved { name } => {
+                command_result::Result::NetworkRemoved(NetworkRemoved { name: Some(name) })
+            }
+            CommandResponse::SnapshotRequested => {
+                command_result::Result::SnapshotRequested(SnapshotRequested {})
+            }
+            CommandResponse::LogsSubscribed { subscription_id } => {
+                command_result::Result::LogsSubscribed(LogsSubscribed {
+                    subscription_id: Some(subscription_id),
+                })
+            }
+            CommandResponse::LogsUnsubscribed { subscription_id } => {
+                command_result::Result::LogsUnsubscribed(LogsUnsubscribed {
+                    subscription_id: Some(subscription_id),
+                })
+            }
+            CommandResponse::ExecStarted { exec_id } => {
+                command_result::Result::ExecStarted(ExecStarted {
+                    exec_id: Some(exec_id),
+                })
+            }
+            CommandResponse::ExecInputAccepted { exec_id } => {
+                command_result::Result::ExecInputAccepted(ExecInputAccepted {
+                    exec_id: Some(exec_id),
+                })
+            }
+            CommandResponse::UploadReceived {
+                upload_id,
+                received_bytes,
+            } => command_result::Result::ArchiveUploadAccepted(ArchiveUploadAccepted {
+                upload_id: Some(upload_id),
+                received_bytes: Some(received_bytes as u64),
+                started: Some(false),
+            }),
+            CommandResponse::UploadStarted { upload_id } => {
+                command_result::Result::ArchiveUploadAccepted(ArchiveUploadAccepted {
+                    upload_id: Some(upload_id),
+                    received_bytes: None,
+                    started: Some(true),
+                })
+            }
+            CommandResponse::DownloadStarted { download_id } => {
+                command_result::Result::ArchiveDownloadStarted(ArchiveDownloadStarted {
+                    download_id: Some(download_id),
+                })
+            }
+            CommandResponse::DesiredStateAccepted { generation } => {
+                command_result::Result::DesiredStateAccepted(DesiredStateAccepted {
+                    generation: Some(generation),
+                })
+            }
+            CommandResponse::Error { message } => command_result::Result::Error(CommandError {
+                message: Some(message),
+            }),
+        };
+
+        CommandResult {
+            command_id,
+            result: Some(result),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct CommandBundle {
-    inner: RemoteCommand,
+    // Boxed, since commands are large and bundles are moved through channels.
+    inner: Box<RemoteCommand>,
     resp_tx: oneshot::Sender<CommandResponse>,
 }
 
 impl CommandBundle {
     pub fn new(cmd: RemoteCommand, tx: oneshot::Sender<CommandResponse>) -> Self {
         Self {
-            inner: cmd,
+            inner: Box::new(cmd),
             resp_tx: tx,
         }
     }
@@ -32,7 +244,7 @@ impl CommandBundle {
 
     #[instrument(skip(self))]
     pub fn reply(self, message: CommandResponse) {
-        if let Err(_) = self.resp_tx.send(message) {
+        if self.resp_tx.send(message).is_err() {
             error!("Failed to send response");
         }
     }
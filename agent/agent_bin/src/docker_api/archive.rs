@@ -0,0 +1,96 @@
+use bollard::{
+    Docker, body_full,
+    query_parameters::{DownloadFromContainerOptionsBuilder, UploadToContainerOptionsBuilder},
+};
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use tracing::{error, instrument};
+
+use crate::docker_api::errors::DockerApiError;
+
+/// Extracts a tar archive into a directory of a container.
+#[instrument(skip(docker, archive), fields(archive_bytes = archive.len()))]
+pub async fn upload(
+    docker: &Docker,
+    container_id: &str,
+    path: &str,
+    archive: Bytes,
+) -> Result<(), DockerApiError> {
+    let options = UploadToContainerOptionsBuilder::new().path(path).build();
+
+    docker
+        .upload_to_container(container_id, Some(options), body_full(archive))
+        .await
+        .map_err(|e| {
+            error!("Archive upload failed: {e}");
+            DockerApiError::ArchiveUploadFailed {
+                container_id: container_id.to_string(),
+                path: path.to_string(),
+            }
+        })
+}
+
+/// Streams a path of a container as a tar archive.
+pub fn download(
+    docker: &Docker,
+    container_id: &str,
+    path: &str,
+) -> impl Stream<Item = Result<Bytes, DockerApiError>> + use<> {
+    let options = DownloadFromContainerOptionsBuilder::new()
+        .path(path)
+        .build();
+    let container_id = container_id.to_string();
+    let path = path.to_string();
+
+    docker
+        .download_from_container(&container_id, Some(options))
+        .map(move |chunk| {
+            chunk.map_err(|e| {
+                error!("Archive download failed: {e}");
+                DockerApiError::ArchiveDownloadFailed {
+                    container_id: container_id.clone(),
+                    path: path.clone(),
+                }
+            })
+        })
+}
+
+/// Paths are resolved by Docker relative to the container's root, but anything other than
+/// a plain absolute path is almost certainly a mistake by the caller.
+pub fn validate_path(path: &str) -> Result<(), DockerApiError> {
+    let reason = if path.is_empty() {
+        "path is empty"
+    } else if !path.starts_with('/') {
+        "path is not absolute"
+    } else if path.split('/').any(|component| component == "..") {
+        "path contains '..'"
+    } else {
+        return Ok(());
+    };
+
+    Err(DockerApiError::InvalidArchivePath {
+        path: path.to_string(),
+        reason,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::docker_api::{archive, errors::DockerApiError};
+
+    #[test]
+    fn validate_path() {
+        assert!(archive::validate_path("/etc/app").is_ok());
+        assert!(archive::validate_path("/").is_ok());
+
+        for path in ["", "etc/app", "/etc/../root", "/.."] {
+            assert!(
+                matches!(
+                    archive::validate_path(path),
+                    Err(DockerApiError::InvalidArchivePath { .. })
+                ),
+                "{path:?} should be rejected"
+            );
+        }
+    }
+}
@@ -1,47 +1,85 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, SystemTime},
+};
 
-use agent_wire::deploything::v1::{ContainerHostConfig, ContainerStatus};
+use agent_wire::deploything::v1::{
+    ContainerHealth, ContainerStatus, PublishedPort, RestartPolicy, RestartPolicyMode, RunParams,
+};
 use bollard::{
     Docker,
     models::ContainerCreateBody,
     query_parameters::{
-        CreateContainerOptions, ListContainersOptionsBuilder, StartContainerOptions,
+        CreateContainerOptionsBuilder, InspectContainerOptions, ListContainersOptionsBuilder,
+        RemoveContainerOptionsBuilder, RestartContainerOptionsBuilder, StartContainerOptions,
         StopContainerOptionsBuilder,
     },
-    secret::{HostConfig, PortBinding},
+    secret::{
+        ContainerState, ContainerSummary, ContainerSummaryStateEnum, HostConfig,
+        RestartPolicy as DockerRestartPolicy, RestartPolicyNameEnum,
+    },
 };
-use tracing::{error, info, instrument};
+use futures_util::future::join_all;
+use tracing::{error, info, instrument, warn};
 
-use crate::docker_api::{errors::DockerApiError, image::ImageRef};
+use crate::docker_api::{
+    errors::DockerApiError,
+    health,
+    image::ImageRef,
+    labels::{self, Replica},
+    mounts, network, ports, resources,
+};
 
 #[instrument(skip(docker), ret)]
 pub async fn create(
     docker: &Docker,
     image_ref: &ImageRef,
-    host_config: Option<&ContainerHostConfig>,
+    params: &RunParams,
+    replica: Option<&Replica>,
 ) -> Result<String, DockerApiError> {
     info!("Creating container");
 
-    let host_config = create_host_config(host_config);
-
-    let mut labels = HashMap::new();
-    labels.insert("deployth.ing/managed".to_string(), "true".to_string());
+    let name = container_name(params)?;
+    let host_config = create_host_config(params)?;
+    let exposed_ports = exposed_ports(&host_config);
+    let env = create_env(params)?;
+    let labels = labels::for_container(params, replica)?;
+    let networking_config = network::networking_config(&params.networks)?;
+    let healthcheck = health::health_config(params.health_check.as_ref())?;
 
     let body = ContainerCreateBody {
         image: Some(image_ref.to_string()),
-        host_config,
+        cmd: non_empty(&params.cmd),
+        entrypoint: non_empty(&params.entrypoint),
+        working_dir: params.working_dir.clone(),
+        user: params.user.clone(),
+        hostname: params.hostname.clone(),
+        env: Some(env),
+        exposed_ports,
+        host_config: Some(host_config),
         labels: Some(labels),
+        networking_config,
+        healthcheck,
         ..Default::default()
     };
 
-    match docker
-        .create_container(None::<CreateContainerOptions>, body)
-        .await
-    {
+    let options = name
+        .as_deref()
+        .map(|name| CreateContainerOptionsBuilder::new().name(name).build());
+
+    match docker.create_container(options, body).await {
         Ok(res) => {
             info!("Container create complete");
             Ok(res.id)
         }
+        Err(bollard::errors::Error::DockerResponseServerError {
+            status_code: 409, ..
+        }) if name.is_some() => {
+            error!("Container create failed: name already in use");
+            Err(DockerApiError::ContainerNameInUse {
+                name: name.unwrap_or_default(),
+            })
+        }
         Err(e) => {
             error!("Container create failed: {e}");
             Err(DockerApiError::ContainerCreateFailed {
@@ -72,14 +110,108 @@ pub async fn start(docker: &Docker, container_id: &str) -> Result<(), DockerApiE
     }
 }
 
+/// How a container is asked to shut down before it is killed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StopOptions {
+    /// How long the container is given to exit after the stop signal.
+    pub timeout: Duration,
+    /// The signal that asks the container to stop. When unset, Docker uses the image's
+    /// `STOPSIGNAL`, or `SIGTERM`.
+    pub signal: Option<String>,
+}
+
+impl Default for StopOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            signal: None,
+        }
+    }
+}
+
+impl StopOptions {
+    /// Returns these options with the timeout and signal replaced by any that are set.
+    pub fn with_overrides(
+        &self,
+        timeout: Option<&prost_types::Duration>,
+        signal: Option<&str>,
+    ) -> Result<Self, DockerApiError> {
+        let timeout = match timeout {
+            Some(timeout) => {
+                Duration::try_from(*timeout).map_err(|_| DockerApiError::InvalidStopOptions {
+                    reason: "timeout is negative",
+                })?
+            }
+            None => self.timeout,
+        };
+
+        let signal = match signal {
+            Some(signal) => Some(signal.to_string()),
+            None => self.signal.clone(),
+        };
+
+        let options = Self { timeout, signal };
+        options.validate()?;
+        Ok(options)
+    }
+
+    fn validate(&self) -> Result<(), DockerApiError> {
+        if self.timeout_secs().is_none() {
+            return Err(DockerApiError::InvalidStopOptions {
+                reason: "timeout is too long",
+            });
+        }
+
+        if let Some(signal) = &self.signal {
+            validate_signal(signal)?;
+        }
+
+        Ok(())
+    }
+
+    /// Docker only accepts whole seconds, so partial seconds are rounded up rather than
+    /// cutting the grace period short.
+    fn timeout_secs(&self) -> Option<i32> {
+        let secs = self.timeout.as_secs() + u64::from(self.timeout.subsec_nanos() > 0);
+        i32::try_from(secs).ok()
+    }
+}
+
+fn validate_signal(signal: &str) -> Result<(), DockerApiError> {
+    if signal.is_empty() || !signal.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(DockerApiError::InvalidStopOptions {
+            reason: "signal must be a name such as SIGTERM, or a number",
+        });
+    }
+
+    Ok(())
+}
+
+/// Parses a `--stop-signal`, so that a bad default is reported at startup rather than by
+/// every stop that relies on it.
+pub fn parse_stop_signal(signal: &str) -> Result<String, String> {
+    validate_signal(signal).map_err(|e| e.to_string())?;
+    Ok(signal.to_string())
+}
+
 #[instrument(skip(docker))]
-pub async fn stop(docker: &Docker, container_id: &str) -> Result<(), DockerApiError> {
+pub async fn stop(
+    docker: &Docker,
+    container_id: &str,
+    options: &StopOptions,
+) -> Result<(), DockerApiError> {
     info!("Stopping container");
 
-    // TODO: allow configuration of timeout
-    let options = StopContainerOptionsBuilder::new().t(10).build();
+    options.validate()?;
+    let mut builder = StopContainerOptionsBuilder::new().t(options.timeout_secs().unwrap_or(0));
+    if let Some(signal) = &options.signal {
+        builder = builder.signal(signal);
+    }
 
-    match docker.stop_container(container_id, Some(options)).await {
+    match docker
+        .stop_container(container_id, Some(builder.build()))
+        .await
+    {
         Ok(_) => {
             info!("Container stopped");
             Ok(())
@@ -93,42 +225,400 @@ pub async fn stop(docker: &Docker, container_id: &str) -> Result<(), DockerApiEr
     }
 }
 
-fn create_host_config(from: Option<&ContainerHostConfig>) -> Option<HostConfig> {
-    let Some(from) = from else {
-        return None;
+#[instrument(skip(docker))]
+pub async fn remove(
+    docker: &Docker,
+    container_id: &str,
+    remove_volumes: bool,
+    force: bool,
+) -> Result<(), DockerApiError> {
+    info!("Removing container");
+
+    let options = RemoveContainerOptionsBuilder::new()
+        .v(remove_volumes)
+        .force(force)
+        .build();
+
+    match docker.remove_container(container_id, Some(options)).await {
+        Ok(_) => {
+            info!("Container removed");
+            Ok(())
+        }
+        Err(e) => {
+            error!("Container remove failed: {e}");
+            Err(DockerApiError::ContainerRemoveFailed {
+                container_id: container_id.to_string(),
+            })
+        }
+    }
+}
+
+#[instrument(skip(docker))]
+pub async fn restart(
+    docker: &Docker,
+    container_id: &str,
+    options: &StopOptions,
+) -> Result<(), DockerApiError> {
+    info!("Restarting container");
+
+    options.validate()?;
+    let mut builder = RestartContainerOptionsBuilder::new().t(options.timeout_secs().unwrap_or(0));
+    if let Some(signal) = &options.signal {
+        builder = builder.signal(signal);
+    }
+
+    match docker
+        .restart_container(container_id, Some(builder.build()))
+        .await
+    {
+        Ok(_) => {
+            info!("Container restarted");
+            Ok(())
+        }
+        Err(e) => {
+            error!("Container restart failed: {e}");
+            Err(DockerApiError::ContainerRestartFailed {
+                container_id: container_id.to_string(),
+            })
+        }
+    }
+}
+
+#[instrument(skip(docker))]
+pub async fn pause(docker: &Docker, container_id: &str) -> Result<(), DockerApiError> {
+    match docker.pause_container(container_id).await {
+        Ok(_) => {
+            info!("Container paused");
+            Ok(())
+        }
+        Err(e) => {
+            error!("Container pause failed: {e}");
+            Err(DockerApiError::ContainerPauseFailed {
+                container_id: container_id.to_string(),
+            })
+        }
+    }
+}
+
+#[instrument(skip(docker))]
+pub async fn unpause(docker: &Docker, container_id: &str) -> Result<(), DockerApiError> {
+    match docker.unpause_container(container_id).await {
+        Ok(_) => {
+            info!("Container unpaused");
+            Ok(())
+        }
+        Err(e) => {
+            error!("Container unpause failed: {e}");
+            Err(DockerApiError::ContainerUnpauseFailed {
+                container_id: container_id.to_string(),
+            })
+        }
+    }
+}
+
+/// Fails unless the container was created by the agent, so that commands acting on existing
+/// containers cannot be used to reach unrelated workloads on the host.
+#[instrument(skip(docker))]
+pub async fn ensure_managed(docker: &Docker, container_id: &str) -> Result<(), DockerApiError> {
+    let inspect = docker
+        .inspect_container(container_id, None::<InspectContainerOptions>)
+        .await
+        .map_err(|e| {
+            error!("Container inspect failed: {e}");
+            DockerApiError::ContainerInspectFailed {
+                container_id: container_id.to_string(),
+            }
+        })?;
+
+    let managed = inspect
+        .config
+        .and_then(|config| config.labels)
+        .is_some_and(|labels| labels::is_managed(&labels));
+
+    if !managed {
+        return Err(DockerApiError::ContainerNotManaged {
+            container_id: container_id.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Containers that belong to an application get a deterministic name, so that a deployment
+/// can be found (and cannot accidentally be started twice) without tracking container IDs.
+fn container_name(params: &RunParams) -> Result<Option<String>, DockerApiError> {
+    let Some(app_name) = &params.app_name else {
+        return Ok(None);
     };
 
-    let mut port_map = HashMap::new();
+    validate_name_part(app_name)?;
+
+    match &params.deployment_id {
+        Some(deployment_id) => {
+            validate_name_part(deployment_id)?;
+            Ok(Some(format!("{app_name}-{deployment_id}")))
+        }
+        None => Ok(Some(app_name.clone())),
+    }
+}
 
-    if let Some(pm) = &from.port_map {
-        let from_port = pm.from.as_ref().cloned().unwrap();
-        let to_port = pm.to.as_ref().cloned().unwrap();
+/// Docker container names must match `[a-zA-Z0-9][a-zA-Z0-9_.-]*`.
+pub fn validate_name_part(part: &str) -> Result<(), DockerApiError> {
+    let valid = part.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && part
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
 
-        port_map.insert(
-            from_port,
-            Some(vec![PortBinding {
-                host_port: Some(to_port),
-                ..Default::default()
-            }]),
-        );
+    if valid {
+        Ok(())
+    } else {
+        Err(DockerApiError::InvalidContainerName {
+            name: part.to_string(),
+        })
+    }
+}
+
+/// An empty list means "use the image's default" rather than "override with nothing".
+fn non_empty(values: &[String]) -> Option<Vec<String>> {
+    (!values.is_empty()).then(|| values.to_vec())
+}
+
+/// Builds the container's `KEY=value` environment from plain variables and secrets.
+/// Variables are sorted by name so that identical `RunParams` produce identical containers.
+fn create_env(params: &RunParams) -> Result<Vec<String>, DockerApiError> {
+    let mut env = Vec::with_capacity(params.env.len() + params.secrets.len());
+
+    for (name, value) in params.env.iter() {
+        validate_env_name(name)?;
+        env.push((name.as_str(), value.as_str()));
+    }
+
+    for (name, secret) in params.secrets.iter() {
+        validate_env_name(name)?;
+        if params.env.contains_key(name) {
+            return Err(DockerApiError::InvalidEnvVar {
+                name: name.clone(),
+                reason: "defined as both a variable and a secret",
+            });
+        }
+        env.push((name.as_str(), secret.value()));
+    }
+
+    env.sort_unstable_by_key(|(name, _)| *name);
+
+    Ok(env
+        .into_iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect())
+}
+
+pub fn validate_env_name(name: &str) -> Result<(), DockerApiError> {
+    let reason = if name.is_empty() {
+        "name is empty"
+    } else if name.contains('=') {
+        "name contains '='"
+    } else {
+        return Ok(());
     };
 
-    let host_config = HostConfig {
-        port_bindings: Some(port_map),
+    Err(DockerApiError::InvalidEnvVar {
+        name: name.to_string(),
+        reason,
+    })
+}
+
+fn create_host_config(params: &RunParams) -> Result<HostConfig, DockerApiError> {
+    let mut host_config = HostConfig {
+        restart_policy: restart_policy(params.restart_policy.as_ref())?,
+        // Attaching to any network replaces the default bridge network.
+        network_mode: params.networks.first().map(|n| n.network().to_string()),
         ..Default::default()
     };
 
-    Some(host_config)
+    let Some(from) = &params.container_host_config else {
+        return Ok(host_config);
+    };
+
+    host_config.port_bindings = Some(ports::port_bindings(&from.port_maps)?);
+    host_config.mounts = Some(mounts::docker_mounts(&from.mounts)?);
+
+    if let Some(limits) = &from.resources {
+        resources::apply(limits, &mut host_config);
+    }
+
+    Ok(host_config)
 }
 
-#[instrument(skip(docker))]
-pub async fn list(docker: &Docker) -> Result<Vec<ContainerStatus>, DockerApiError> {
-    let options = ListContainersOptionsBuilder::new().all(true).build();
+fn restart_policy(
+    from: Option<&RestartPolicy>,
+) -> Result<Option<DockerRestartPolicy>, DockerApiError> {
+    let Some(from) = from else {
+        return Ok(None);
+    };
+
+    let name = match from.mode() {
+        RestartPolicyMode::Unspecified | RestartPolicyMode::No => RestartPolicyNameEnum::NO,
+        RestartPolicyMode::OnFailure => RestartPolicyNameEnum::ON_FAILURE,
+        RestartPolicyMode::UnlessStopped => RestartPolicyNameEnum::UNLESS_STOPPED,
+        RestartPolicyMode::Always => RestartPolicyNameEnum::ALWAYS,
+    };
+
+    match from.max_retries {
+        Some(retries) if retries < 0 => Err(DockerApiError::InvalidRestartPolicy {
+            reason: "max retries must not be negative",
+        }),
+        Some(retries) if retries > 0 && name != RestartPolicyNameEnum::ON_FAILURE => {
+            Err(DockerApiError::InvalidRestartPolicy {
+                reason: "max retries is only valid with the on-failure mode",
+            })
+        }
+        max_retries => Ok(Some(DockerRestartPolicy {
+            name: Some(name),
+            maximum_retry_count: max_retries,
+        })),
+    }
+}
+
+/// Docker only publishes ports that are exposed, so every bound port is exposed explicitly
+/// in case the image does not `EXPOSE` it itself.
+fn exposed_ports(host_config: &HostConfig) -> Option<HashMap<String, HashMap<(), ()>>> {
+    let port_bindings = host_config.port_bindings.as_ref()?;
+
+    Some(
+        port_bindings
+            .keys()
+            .map(|port| (port.clone(), HashMap::new()))
+            .collect(),
+    )
+}
+
+/// Returns the host ports a started container has actually been published on, including
+/// any that Docker picked at random.
+#[instrument(skip(docker), ret)]
+pub async fn published_ports(
+    docker: &Docker,
+    container_id: &str,
+) -> Result<Vec<PublishedPort>, DockerApiError> {
+    match docker
+        .inspect_container(container_id, None::<InspectContainerOptions>)
+        .await
+    {
+        Ok(inspect) => Ok(inspect
+            .network_settings
+            .and_then(|settings| settings.ports)
+            .map(|ports| ports::published_ports(&ports))
+            .unwrap_or_default()),
+        Err(e) => {
+            error!("Container inspect failed: {e}");
+            Err(DockerApiError::ContainerInspectFailed {
+                container_id: container_id.to_string(),
+            })
+        }
+    }
+}
+
+/// Details of listed containers that are only available by inspecting them, keyed by
+/// container ID. A container is only inspected again once its listed state or status
+/// changes, which covers restarts and health changes, so that listing does not cost a
+/// Docker call per container.
+#[derive(Debug, Default)]
+pub struct InspectCache {
+    inspected: HashMap<String, Inspected>,
+}
+
+#[derive(Debug)]
+struct Inspected {
+    /// The listed state and status the details were inspected for.
+    listed: Listed,
+    restart_count: Option<i64>,
+    state: Option<ContainerState>,
+}
+
+type Listed = (Option<ContainerSummaryStateEnum>, Option<String>);
+
+fn listed(summary: &ContainerSummary) -> Listed {
+    (summary.state, summary.status.clone())
+}
+
+impl InspectCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn needs_inspect(&self, summary: &ContainerSummary) -> bool {
+        let Some(id) = &summary.id else {
+            return false;
+        };
+
+        self.inspected
+            .get(id)
+            .is_none_or(|inspected| inspected.listed != listed(summary))
+    }
+
+    /// Inspects the containers whose details are missing or out of date, and forgets those
+    /// that are no longer listed.
+    async fn refresh(&mut self, docker: &Docker, containers: &[ContainerSummary]) {
+        let listed_ids: HashSet<_> = containers.iter().filter_map(|c| c.id.as_ref()).collect();
+        self.inspected.retain(|id, _| listed_ids.contains(id));
+
+        let stale: Vec<_> = containers
+            .iter()
+            .filter(|summary| self.needs_inspect(summary))
+            .collect();
+
+        let inspected = join_all(stale.into_iter().map(|summary| inspect(docker, summary))).await;
+
+        for (id, inspected) in inspected.into_iter().flatten() {
+            self.inspected.insert(id, inspected);
+        }
+    }
+}
+
+async fn inspect(docker: &Docker, summary: &ContainerSummary) -> Option<(String, Inspected)> {
+    let id = summary.id.as_ref()?;
+
+    match docker
+        .inspect_container(id, None::<InspectContainerOptions>)
+        .await
+    {
+        Ok(inspect) => Some((
+            id.clone(),
+            Inspected {
+                listed: listed(summary),
+                restart_count: inspect.restart_count,
+                state: inspect.state,
+            },
+        )),
+        // The container may have been removed since it was listed.
+        Err(e) => {
+            warn!("Container inspect failed for {id}: {e}");
+            None
+        }
+    }
+}
+
+#[instrument(skip(docker, inspected))]
+pub async fn list(
+    docker: &Docker,
+    include_unmanaged: bool,
+    inspected: &mut InspectCache,
+) -> Result<Vec<ContainerStatus>, DockerApiError> {
+    let mut options = ListContainersOptionsBuilder::new().all(true);
+    if !include_unmanaged {
+        options = options.filters(&labels::managed_filter());
+    }
+    let options = options.build();
 
     match docker.list_containers(Some(options)).await {
         Ok(containers) => {
             info!("List containers complete");
-            let containers = containers.iter().map(|c| c.into()).collect();
+            inspected.refresh(docker, &containers).await;
+
+            let now = SystemTime::now();
+            let containers = containers
+                .iter()
+                .map(|summary| status(summary, inspected, now))
+                .collect();
             Ok(containers)
         }
         Err(e) => {
@@ -137,3 +627,297 @@ pub async fn list(docker: &Docker) -> Result<Vec<ContainerStatus>, DockerApiErro
         }
     }
 }
+
+/// Builds the status of a listed container, including any details it was inspected for.
+fn status(
+    summary: &ContainerSummary,
+    inspected: &InspectCache,
+    now: SystemTime,
+) -> ContainerStatus {
+    let mut status = ContainerStatus::from(summary);
+
+    if let Some(labels) = &summary.labels {
+        status.app_name = labels.get(labels::APP).cloned();
+        status.deployment_id = labels.get(labels::DEPLOYMENT).cloned();
+    }
+    status.managed = Some(summary.labels.as_ref().is_some_and(labels::is_managed));
+
+    let Some(inspected) = summary
+        .id
+        .as_ref()
+        .and_then(|id| inspected.inspected.get(id))
+    else {
+        return status;
+    };
+
+    status.restart_count = inspected.restart_count;
+    if let Some(state) = &inspected.state {
+        status.apply_inspected_state(state);
+        status.set_uptime(now);
+    }
+
+    let health = inspected
+        .state
+        .as_ref()
+        .and_then(|state| state.health.as_ref())
+        .and_then(|health| health.status)
+        .map_or(ContainerHealth::None, ContainerHealth::from);
+    status.health = Some(health as i32);
+
+    status
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        time::{Duration, SystemTime},
+    };
+
+    use agent_wire::deploything::v1::{
+        ContainerHealth, ContainerState, RestartPolicy, RestartPolicyMode, RunParams, SecretValue,
+    };
+    use bollard::secret::{
+        ContainerState as DockerContainerState, ContainerStateStatusEnum, ContainerSummary,
+        ContainerSummaryStateEnum, Health, HealthStatusEnum, RestartPolicyNameEnum,
+    };
+
+    use crate::docker_api::{
+        container::{
+            InspectCache, Inspected, StopOptions, container_name, create_env, listed,
+            parse_stop_signal, restart_policy, status,
+        },
+        errors::DockerApiError,
+    };
+
+    fn summary(state: ContainerSummaryStateEnum, status_text: &str) -> ContainerSummary {
+        ContainerSummary {
+            id: Some("abc123".to_string()),
+            state: Some(state),
+            status: Some(status_text.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn cache_for(summary: &ContainerSummary, state: DockerContainerState) -> InspectCache {
+        let mut cache = InspectCache::new();
+        cache.inspected.insert(
+            "abc123".to_string(),
+            Inspected {
+                listed: listed(summary),
+                restart_count: Some(2),
+                state: Some(state),
+            },
+        );
+        cache
+    }
+
+    #[test]
+    fn inspects_again_only_when_listing_changes() {
+        let running = summary(ContainerSummaryStateEnum::RUNNING, "Up 2 hours (healthy)");
+        let cache = cache_for(&running, DockerContainerState::default());
+
+        assert!(!cache.needs_inspect(&running));
+        assert!(cache.needs_inspect(&summary(
+            ContainerSummaryStateEnum::RUNNING,
+            "Up 2 hours (unhealthy)"
+        )));
+        assert!(cache.needs_inspect(&summary(
+            ContainerSummaryStateEnum::EXITED,
+            "Exited (1) 1 second ago"
+        )));
+        assert!(InspectCache::new().needs_inspect(&running));
+    }
+
+    #[test]
+    fn status_uses_cached_details_and_listed_state() {
+        let running = summary(ContainerSummaryStateEnum::RUNNING, "Up 2 hours (healthy)");
+        let cache = cache_for(
+            &running,
+            DockerContainerState {
+                status: Some(ContainerStateStatusEnum::RESTARTING),
+                health: Some(Health {
+                    status: Some(HealthStatusEnum::HEALTHY),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        );
+
+        let status = status(&running, &cache, SystemTime::now());
+
+        assert_eq!(status.container_state(), ContainerState::Running);
+        assert_eq!(status.health(), ContainerHealth::Healthy);
+        assert_eq!(status.restart_count, Some(2));
+    }
+
+    #[test]
+    fn env_merges_variables_and_secrets_in_name_order() {
+        let params = RunParams {
+            env: HashMap::from([
+                ("PORT".to_string(), "8080".to_string()),
+                ("APP_ENV".to_string(), "production".to_string()),
+            ]),
+            secrets: HashMap::from([(
+                "DATABASE_PASSWORD".to_string(),
+                SecretValue {
+                    value: Some("hunter2".into()),
+                },
+            )]),
+            ..Default::default()
+        };
+
+        let env = create_env(&params).unwrap();
+
+        assert_eq!(
+            vec![
+                "APP_ENV=production",
+                "DATABASE_PASSWORD=hunter2",
+                "PORT=8080"
+            ],
+            env
+        );
+    }
+
+    #[test]
+    fn env_rejects_invalid_names() {
+        let params = RunParams {
+            env: HashMap::from([("FOO=BAR".to_string(), "baz".to_string())]),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            create_env(&params),
+            Err(DockerApiError::InvalidEnvVar { .. })
+        ));
+    }
+
+    #[test]
+    fn env_rejects_secrets_shadowing_variables() {
+        let params = RunParams {
+            env: HashMap::from([("TOKEN".to_string(), "plain".to_string())]),
+            secrets: HashMap::from([("TOKEN".to_string(), SecretValue::default())]),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            create_env(&params),
+            Err(DockerApiError::InvalidEnvVar { .. })
+        ));
+    }
+
+    #[test]
+    fn restart_policy_maps_modes() {
+        let policy = restart_policy(Some(&RestartPolicy {
+            mode: Some(RestartPolicyMode::OnFailure as i32),
+            max_retries: Some(5),
+        }))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(Some(RestartPolicyNameEnum::ON_FAILURE), policy.name);
+        assert_eq!(Some(5), policy.maximum_retry_count);
+
+        let policy = restart_policy(Some(&RestartPolicy::default()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some(RestartPolicyNameEnum::NO), policy.name);
+    }
+
+    #[test]
+    fn restart_policy_rejects_retries_without_on_failure() {
+        let result = restart_policy(Some(&RestartPolicy {
+            mode: Some(RestartPolicyMode::Always as i32),
+            max_retries: Some(3),
+        }));
+
+        assert!(matches!(
+            result,
+            Err(DockerApiError::InvalidRestartPolicy { .. })
+        ));
+    }
+
+    #[test]
+    fn container_name_is_derived_from_app_and_deployment() {
+        let mut params = RunParams::default();
+        assert_eq!(None, container_name(&params).unwrap());
+
+        params.app_name = Some("web".into());
+        assert_eq!(Some("web".to_string()), container_name(&params).unwrap());
+
+        params.deployment_id = Some("v42".into());
+        assert_eq!(
+            Some("web-v42".to_string()),
+            container_name(&params).unwrap()
+        );
+
+        params.deployment_id = Some("v42/../../etc".into());
+        assert!(matches!(
+            container_name(&params),
+            Err(DockerApiError::InvalidContainerName { .. })
+        ));
+    }
+
+    #[test]
+    fn stop_options_overrides_and_rounding() {
+        let defaults = StopOptions {
+            timeout: Duration::from_secs(30),
+            signal: Some("SIGQUIT".to_string()),
+        };
+
+        assert_eq!(defaults.with_overrides(None, None).unwrap(), defaults);
+
+        let timeout = prost_types::Duration {
+            seconds: 120,
+            nanos: 500_000_000,
+        };
+        let options = defaults
+            .with_overrides(Some(&timeout), Some("SIGTERM"))
+            .unwrap();
+
+        assert_eq!(options.signal.as_deref(), Some("SIGTERM"));
+        assert_eq!(options.timeout_secs(), Some(121));
+    }
+
+    #[test]
+    fn stop_options_rejects_invalid_overrides() {
+        let defaults = StopOptions::default();
+        let negative = prost_types::Duration {
+            seconds: -1,
+            nanos: 0,
+        };
+        let too_long = prost_types::Duration {
+            seconds: i64::from(i32::MAX) + 1,
+            nanos: 0,
+        };
+
+        for (case, timeout, signal) in [
+            ("negative timeout", Some(&negative), None),
+            ("overlong timeout", Some(&too_long), None),
+            ("empty signal", None, Some("")),
+            ("signal with punctuation", None, Some("SIGTERM; rm")),
+        ] {
+            assert!(
+                matches!(
+                    defaults.with_overrides(timeout, signal),
+                    Err(DockerApiError::InvalidStopOptions { .. })
+                ),
+                "{case} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn stop_signal_is_parsed_at_startup() {
+        assert_eq!(parse_stop_signal("SIGINT"), Ok("SIGINT".to_string()));
+        assert_eq!(parse_stop_signal("15"), Ok("15".to_string()));
+        assert!(
+            parse_stop_signal("").is_err(),
+            "an empty signal should be rejected"
+        );
+        assert!(
+            parse_stop_signal("SIG TERM").is_err(),
+            "a signal with a space should be rejected"
+        );
+    }
+}
@@ -5,18 +5,145 @@ pub enum DockerApiError {
     #[error("failed to pull {image}:{tag}")]
     ImagePullFailed { image: String, tag: String },
 
+    #[error("image {image} is not present locally and the pull policy forbids pulling it")]
+    ImageNotPresent { image: String },
+
+    #[error("failed to build image {image}")]
+    ImageBuildFailed { image: String },
+
+    #[error("invalid environment variable {name:?}: {reason}")]
+    InvalidEnvVar { name: String, reason: &'static str },
+
+    #[error("invalid port map for {from:?}: {reason}")]
+    InvalidPortMap { from: String, reason: &'static str },
+
+    #[error("invalid mount at {target:?}: {reason}")]
+    InvalidMount {
+        target: String,
+        reason: &'static str,
+    },
+
+    #[error("{path:?} is not on the bind mount allowlist")]
+    BindMountNotAllowed { path: String },
+
+    #[error("invalid resource limits: {reason}")]
+    InvalidResourceLimits { reason: String },
+
+    #[error("requested {resource} ({requested}) exceeds the agent maximum ({maximum})")]
+    ResourceLimitExceeded {
+        resource: &'static str,
+        requested: String,
+        maximum: String,
+    },
+
+    #[error("invalid restart policy: {reason}")]
+    InvalidRestartPolicy { reason: &'static str },
+
+    #[error("invalid health check: {reason}")]
+    InvalidHealthCheck { reason: &'static str },
+
+    #[error("invalid container name component {name:?}")]
+    InvalidContainerName { name: String },
+
+    #[error("a container named {name} already exists")]
+    ContainerNameInUse { name: String },
+
+    #[error("label {key:?} uses the reserved deployth.ing/ prefix")]
+    ReservedLabel { key: String },
+
     #[error("failed to create container for image {image}")]
     ContainerCreateFailed { image: String },
 
     #[error("failed to start container {container_id}")]
     ContainerStartFailed { container_id: String },
 
+    #[error("failed to inspect container {container_id}")]
+    ContainerInspectFailed { container_id: String },
+
+    #[error("invalid stop options: {reason}")]
+    InvalidStopOptions { reason: &'static str },
+
     #[error("failed to stop container {container_id}")]
     ContainerStopFailed { container_id: String },
 
+    #[error("failed to remove container {container_id}")]
+    ContainerRemoveFailed { container_id: String },
+
+    #[error("failed to restart container {container_id}")]
+    ContainerRestartFailed { container_id: String },
+
+    #[error("failed to pause container {container_id}")]
+    ContainerPauseFailed { container_id: String },
+
+    #[error("failed to unpause container {container_id}")]
+    ContainerUnpauseFailed { container_id: String },
+
+    #[error("container {container_id} is not managed by the agent")]
+    ContainerNotManaged { container_id: String },
+
+    #[error("invalid log subscription: {reason}")]
+    InvalidLogSubscription { reason: &'static str },
+
+    #[error("failed to read logs of container {container_id}")]
+    ContainerLogsFailed { container_id: String },
+
+    #[error("{program:?} is not on the exec allowlist")]
+    ExecNotAllowed { program: String },
+
+    #[error("invalid exec: {reason}")]
+    InvalidExec { reason: &'static str },
+
+    #[error("failed to create exec in container {container_id}")]
+    ExecCreateFailed { container_id: String },
+
+    #[error("failed to start exec in container {container_id}")]
+    ExecStartFailed { container_id: String },
+
+    #[error("failed to read output of exec {exec_id}")]
+    ExecOutputFailed { exec_id: String },
+
+    #[error("failed to inspect exec {exec_id}")]
+    ExecInspectFailed { exec_id: String },
+
+    #[error("invalid archive path {path:?}: {reason}")]
+    InvalidArchivePath { path: String, reason: &'static str },
+
+    #[error("failed to upload archive to {path} in container {container_id}")]
+    ArchiveUploadFailed { container_id: String, path: String },
+
+    #[error("failed to download {path} from container {container_id}")]
+    ArchiveDownloadFailed { container_id: String, path: String },
+
     #[error("failed to list containers")]
     ListContainersFailed,
 
+    #[error("failed to create volume {name}")]
+    VolumeCreateFailed { name: String },
+
+    #[error("failed to list volumes")]
+    ListVolumesFailed,
+
+    #[error("volume {name} is not managed by the agent")]
+    VolumeNotManaged { name: String },
+
+    #[error("failed to remove volume {name}")]
+    VolumeRemoveFailed { name: String },
+
+    #[error("invalid network {name:?}: {reason}")]
+    InvalidNetwork { name: String, reason: &'static str },
+
+    #[error("failed to create network {name}")]
+    NetworkCreateFailed { name: String },
+
+    #[error("network {name} is not managed by the agent")]
+    NetworkNotManaged { name: String },
+
+    #[error("failed to inspect network {name}")]
+    NetworkInspectFailed { name: String },
+
+    #[error("failed to remove network {name}")]
+    NetworkRemoveFailed { name: String },
+
     #[error("failed to start monitoring events")]
     MonitorEventsFailed,
 }
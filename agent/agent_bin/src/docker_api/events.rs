@@ -1,30 +1,89 @@
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
-use bollard::{Docker, query_parameters::EventsOptionsBuilder};
+use agent_wire::deploything::v1::{
+    ContainerDied, ContainerEvent, ContainerHealth, ContainerHealthChanged, container_event,
+};
+use bollard::{Docker, query_parameters::EventsOptionsBuilder, secret::EventMessage};
+use prost_types::Timestamp;
+use tokio::sync::mpsc::Sender;
 use tokio_stream::StreamExt;
-use tracing::{error, info, instrument};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, info, instrument, warn};
 
-use crate::docker_api::errors::DockerApiError;
+use crate::{
+    docker_api::{errors::DockerApiError, labels},
+    snapshot::SnapshotTrigger,
+    ws::encode_message,
+};
 
+/// The container events forwarded to the control plane.
+const FORWARDED_EVENTS: [&str; 5] = ["start", "die", "oom", "health_status", "destroy"];
+
+const MIN_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(30);
+
+/// Forwards lifecycle events for managed containers to the control plane as they happen.
 pub struct DockerEventsHandler<'a> {
     docker: &'a Docker,
+    msg_tx: Sender<Message>,
+    snapshots: SnapshotTrigger,
+    /// The time, in nanoseconds since the epoch, of the last event seen. Used to resume
+    /// the event stream where it left off after it fails.
+    since_nanos: i64,
 }
 
 impl<'a> DockerEventsHandler<'a> {
-    pub fn new(docker: &'a Docker) -> Self {
-        Self { docker }
+    pub fn new(docker: &'a Docker, msg_tx: Sender<Message>, snapshots: SnapshotTrigger) -> Self {
+        let since_nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("failed to get current epoch time")
+            .as_nanos() as i64;
+
+        Self {
+            docker,
+            msg_tx,
+            snapshots,
+            since_nanos,
+        }
+    }
+
+    /// Listens for events until the message channel is closed, resubscribing whenever
+    /// the event stream fails or ends.
+    pub async fn listen(&mut self) {
+        let mut delay = MIN_RESUBSCRIBE_DELAY;
+
+        loop {
+            let since_nanos = self.since_nanos;
+
+            match self.subscribe().await {
+                Ok(()) => return,
+                Err(e) => warn!("{e}, resubscribing in {delay:?}"),
+            }
+
+            tokio::time::sleep(delay).await;
+
+            delay = if self.since_nanos == since_nanos {
+                (delay * 2).min(MAX_RESUBSCRIBE_DELAY)
+            } else {
+                MIN_RESUBSCRIBE_DELAY
+            };
+        }
     }
 
+    /// Forwards events from a single subscription. Returns `Ok` once the message channel
+    /// is closed, and an error if the event stream fails or ends.
     #[instrument(skip(self))]
-    pub async fn listen(&mut self) -> Result<(), DockerApiError> {
-        let now = SystemTime::now();
-        let epoch_time = now
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("failed to get current epoch time")
-            .as_secs();
+    async fn subscribe(&mut self) -> Result<(), DockerApiError> {
+        let mut filters = labels::managed_filter();
+        filters.insert("type", vec!["container".to_string()]);
+        filters.insert(
+            "event",
+            FORWARDED_EVENTS.iter().map(|e| e.to_string()).collect(),
+        );
 
         let options = EventsOptionsBuilder::new()
-            .since(&epoch_time.to_string())
+            .since(&since(self.since_nanos))
+            .filters(&filters)
             .build();
         let mut events_stream = self.docker.events(Some(options));
 
@@ -34,6 +93,25 @@ impl<'a> DockerEventsHandler<'a> {
             match events {
                 Ok(events) => {
                     info!("{events:?}");
+
+                    // Resubscribing replays events at the cursor itself.
+                    if let Some(time_nano) = events.time_nano {
+                        if time_nano <= self.since_nanos {
+                            continue;
+                        }
+                        self.since_nanos = time_nano;
+                    }
+
+                    let Some(event) = container_event(&events) else {
+                        continue;
+                    };
+
+                    if self.msg_tx.send(encode_message(event)).await.is_err() {
+                        error!("Message channel closed, no longer forwarding events");
+                        return Ok(());
+                    }
+
+                    self.snapshots.request();
                 }
                 Err(e) => {
                     error!("Failed to monitor events: {e}");
@@ -42,6 +120,162 @@ impl<'a> DockerEventsHandler<'a> {
             }
         }
 
-        Ok(())
+        error!("Event stream ended");
+        Err(DockerApiError::MonitorEventsFailed)
+    }
+}
+
+/// Formats a cursor as the `since` parameter, i.e. fractional seconds since the epoch.
+fn since(nanos: i64) -> String {
+    format!(
+        "{}.{:09}",
+        nanos.div_euclid(1_000_000_000),
+        nanos.rem_euclid(1_000_000_000)
+    )
+}
+
+/// Converts a Docker container event into the event reported to the control plane, if it
+/// is one the control plane cares about.
+fn container_event(message: &EventMessage) -> Option<ContainerEvent> {
+    let action = message.action.as_deref()?;
+    let attributes = message
+        .actor
+        .as_ref()
+        .and_then(|actor| actor.attributes.as_ref());
+    let attribute = |key: &str| {
+        attributes
+            .and_then(|attributes| attributes.get(key))
+            .cloned()
+    };
+
+    let event = match action {
+        "start" => container_event::Event::Started(()),
+        "die" => container_event::Event::Died(ContainerDied {
+            exit_code: attribute("exitCode").and_then(|code| code.parse().ok()),
+        }),
+        "oom" => container_event::Event::OomKilled(()),
+        "destroy" => container_event::Event::Destroyed(()),
+        _ => {
+            let health = action.strip_prefix("health_status: ")?;
+            container_event::Event::HealthChanged(ContainerHealthChanged {
+                health: Some(parse_health(health) as i32),
+            })
+        }
+    };
+
+    let time = message.time_nano.map(|nanos| Timestamp {
+        seconds: nanos.div_euclid(1_000_000_000),
+        nanos: nanos.rem_euclid(1_000_000_000) as i32,
+    });
+
+    Some(ContainerEvent {
+        container_id: message.actor.as_ref().and_then(|actor| actor.id.clone()),
+        time,
+        app_name: attribute(labels::APP),
+        deployment_id: attribute(labels::DEPLOYMENT),
+        event: Some(event),
+    })
+}
+
+fn parse_health(health: &str) -> ContainerHealth {
+    match health {
+        "starting" => ContainerHealth::Starting,
+        "healthy" => ContainerHealth::Healthy,
+        "unhealthy" => ContainerHealth::Unhealthy,
+        _ => ContainerHealth::Unspecified,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use agent_wire::deploything::v1::{
+        ContainerDied, ContainerHealth, ContainerHealthChanged, container_event,
+    };
+    use bollard::secret::{EventActor, EventMessage, EventMessageTypeEnum};
+
+    use crate::docker_api::events;
+
+    fn event(action: &str, attributes: &[(&str, &str)]) -> EventMessage {
+        let attributes = attributes
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>();
+
+        EventMessage {
+            typ: Some(EventMessageTypeEnum::CONTAINER),
+            action: Some(action.to_string()),
+            actor: Some(EventActor {
+                id: Some("abc123".to_string()),
+                attributes: Some(attributes),
+            }),
+            time_nano: Some(1_700_000_000_250_000_000),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn health_status_event() {
+        let event = events::container_event(&event("health_status: unhealthy", &[])).unwrap();
+
+        assert_eq!(event.container_id.as_deref(), Some("abc123"));
+        let time = event.time.unwrap();
+        assert_eq!((time.seconds, time.nanos), (1_700_000_000, 250_000_000));
+        assert_eq!(
+            event.event,
+            Some(container_event::Event::HealthChanged(
+                ContainerHealthChanged {
+                    health: Some(ContainerHealth::Unhealthy as i32)
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn die_event_has_exit_code_and_app() {
+        let event = events::container_event(&event(
+            "die",
+            &[
+                ("exitCode", "137"),
+                ("deployth.ing/app", "web"),
+                ("deployth.ing/deployment", "abc"),
+            ],
+        ))
+        .unwrap();
+
+        assert_eq!(event.app_name.as_deref(), Some("web"));
+        assert_eq!(event.deployment_id.as_deref(), Some("abc"));
+        assert_eq!(
+            event.event,
+            Some(container_event::Event::Died(ContainerDied {
+                exit_code: Some(137)
+            }))
+        );
+    }
+
+    #[test]
+    fn lifecycle_events() {
+        for (action, expected) in [
+            ("start", container_event::Event::Started(())),
+            ("oom", container_event::Event::OomKilled(())),
+            ("destroy", container_event::Event::Destroyed(())),
+        ] {
+            let event = events::container_event(&event(action, &[])).unwrap();
+            assert_eq!(event.event, Some(expected));
+        }
+    }
+
+    #[test]
+    fn other_events_are_ignored() {
+        assert!(events::container_event(&event("exec_start: sh", &[])).is_none());
+    }
+
+    #[test]
+    fn since_has_nanosecond_precision() {
+        assert_eq!(
+            events::since(1_700_000_000_000_000_042),
+            "1700000000.000000042"
+        );
     }
 }
@@ -0,0 +1,279 @@
+use std::{collections::HashSet, pin::Pin};
+
+use agent_wire::deploything::v1::{ExecParams, LogStream};
+use bollard::{
+    Docker,
+    container::LogOutput,
+    exec::{StartExecOptions, StartExecResults},
+    models::ExecConfig,
+};
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use tokio::io::AsyncWrite;
+use tracing::{error, instrument};
+
+use crate::docker_api::{container, errors::DockerApiError};
+
+/// The programs that may be run inside containers. An empty allowlist disables exec.
+#[derive(Debug, Default, Clone)]
+pub struct ExecAllowlist {
+    programs: HashSet<String>,
+}
+
+impl ExecAllowlist {
+    pub fn new(programs: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            programs: programs.into_iter().collect(),
+        }
+    }
+
+    /// Programs are matched exactly, so allowing `sh` does not allow `/bin/sh`.
+    pub fn check(&self, cmd: &[String]) -> Result<(), DockerApiError> {
+        let program = cmd.first().map(String::as_str).unwrap_or_default();
+
+        if !self.programs.contains(program) {
+            return Err(DockerApiError::ExecNotAllowed {
+                program: program.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+type OutputStream = Pin<Box<dyn Stream<Item = Result<(LogStream, Bytes), DockerApiError>> + Send>>;
+
+/// A process running inside a container, with its output and standard input attached.
+pub struct Exec {
+    pub id: String,
+    pub output: OutputStream,
+    pub input: Pin<Box<dyn AsyncWrite + Send>>,
+}
+
+#[instrument(skip(docker, config))]
+pub async fn start(
+    docker: &Docker,
+    container_id: &str,
+    config: ExecConfig,
+) -> Result<Exec, DockerApiError> {
+    let created = docker
+        .create_exec(container_id, config)
+        .await
+        .map_err(|e| {
+            error!("Exec create failed: {e}");
+            DockerApiError::ExecCreateFailed {
+                container_id: container_id.to_string(),
+            }
+        })?;
+
+    let options = StartExecOptions {
+        detach: false,
+        tty: false,
+        output_capacity: None,
+    };
+
+    let started = docker.start_exec(&created.id, Some(options)).await;
+
+    let (output, input) = match started {
+        Ok(StartExecResults::Attached { output, input }) => (output, input),
+        Ok(StartExecResults::Detached) => {
+            error!("Exec started detached");
+            return Err(DockerApiError::ExecStartFailed {
+                container_id: container_id.to_string(),
+            });
+        }
+        Err(e) => {
+            error!("Exec start failed: {e}");
+            return Err(DockerApiError::ExecStartFailed {
+                container_id: container_id.to_string(),
+            });
+        }
+    };
+
+    let exec_id = created.id.clone();
+    let output = output.map(move |output| match output {
+        Ok(LogOutput::StdOut { message } | LogOutput::Console { message }) => {
+            Ok((LogStream::Stdout, message))
+        }
+        Ok(LogOutput::StdErr { message }) => Ok((LogStream::Stderr, message)),
+        Ok(LogOutput::StdIn { message }) => Ok((LogStream::Unspecified, message)),
+        Err(e) => {
+            error!("Exec output failed: {e}");
+            Err(DockerApiError::ExecOutputFailed {
+                exec_id: exec_id.clone(),
+            })
+        }
+    });
+
+    Ok(Exec {
+        id: created.id,
+        output: Box::pin(output),
+        input,
+    })
+}
+
+/// Returns the exit code of the exec's process, or `None` if it is still running.
+#[instrument(skip(docker))]
+pub async fn exit_code(docker: &Docker, exec_id: &str) -> Result<Option<i64>, DockerApiError> {
+    let inspect = docker.inspect_exec(exec_id).await.map_err(|e| {
+        error!("Exec inspect failed: {e}");
+        DockerApiError::ExecInspectFailed {
+            exec_id: exec_id.to_string(),
+        }
+    })?;
+
+    if inspect.running == Some(true) {
+        return Ok(None);
+    }
+
+    Ok(inspect.exit_code)
+}
+
+pub fn exec_config(
+    allowlist: &ExecAllowlist,
+    params: &ExecParams,
+) -> Result<ExecConfig, DockerApiError> {
+    let reason = if params.exec_id().is_empty() {
+        Some("exec ID is empty")
+    } else if params.container_id().is_empty() {
+        Some("container ID is empty")
+    } else if params.cmd.is_empty() {
+        Some("command is empty")
+    } else {
+        None
+    };
+
+    if let Some(reason) = reason {
+        return Err(DockerApiError::InvalidExec { reason });
+    }
+
+    allowlist.check(&params.cmd)?;
+
+    let mut env = Vec::with_capacity(params.env.len());
+    for (name, value) in params.env.iter() {
+        container::validate_env_name(name)?;
+        env.push(format!("{name}={value}"));
+    }
+    env.sort_unstable();
+
+    let interactive = params.interactive();
+
+    Ok(ExecConfig {
+        attach_stdin: Some(interactive),
+        attach_stdout: Some(true),
+        attach_stderr: Some(true),
+        tty: Some(false),
+        env: (!env.is_empty()).then_some(env),
+        cmd: Some(params.cmd.clone()),
+        user: params.user.clone().filter(|user| !user.is_empty()),
+        working_dir: params.working_dir.clone().filter(|dir| !dir.is_empty()),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use agent_wire::deploything::v1::ExecParams;
+
+    use crate::docker_api::{
+        errors::DockerApiError,
+        exec::{self, ExecAllowlist},
+    };
+
+    fn allowlist() -> ExecAllowlist {
+        ExecAllowlist::new(["ls".to_string(), "/bin/sh".to_string()])
+    }
+
+    fn params(cmd: &[&str]) -> ExecParams {
+        ExecParams {
+            exec_id: Some("exec".to_string()),
+            container_id: Some("abc123".to_string()),
+            cmd: cmd.iter().map(|arg| arg.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn allowlist_matches_program_exactly() {
+        let allowlist = allowlist();
+
+        assert!(
+            allowlist
+                .check(&["ls".to_string(), "-l".to_string()])
+                .is_ok()
+        );
+        assert!(allowlist.check(&["/bin/sh".to_string()]).is_ok());
+
+        for cmd in [vec!["/bin/ls".to_string()], vec!["sh".to_string()], vec![]] {
+            assert!(
+                matches!(
+                    allowlist.check(&cmd),
+                    Err(DockerApiError::ExecNotAllowed { .. })
+                ),
+                "{cmd:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn empty_allowlist_disables_exec() {
+        assert!(matches!(
+            exec::exec_config(&ExecAllowlist::default(), &params(&["ls"])),
+            Err(DockerApiError::ExecNotAllowed { .. })
+        ));
+    }
+
+    #[test]
+    fn config_attaches_stdin_only_when_interactive() {
+        let mut params = params(&["ls", "/data"]);
+        params.env = HashMap::from([
+            ("TERM".to_string(), "dumb".to_string()),
+            ("LANG".to_string(), "C".to_string()),
+        ]);
+        params.working_dir = Some("/srv".to_string());
+
+        let config = exec::exec_config(&allowlist(), &params).unwrap();
+
+        assert_eq!(config.attach_stdin, Some(false));
+        assert_eq!(config.attach_stdout, Some(true));
+        assert_eq!(
+            config.cmd,
+            Some(vec!["ls".to_string(), "/data".to_string()])
+        );
+        assert_eq!(
+            config.env,
+            Some(vec!["LANG=C".to_string(), "TERM=dumb".to_string()])
+        );
+        assert_eq!(config.working_dir.as_deref(), Some("/srv"));
+        assert_eq!(config.user, None);
+
+        params.interactive = Some(true);
+        let config = exec::exec_config(&allowlist(), &params).unwrap();
+        assert_eq!(config.attach_stdin, Some(true));
+    }
+
+    #[test]
+    fn config_requires_exec_id() {
+        let mut params = params(&["ls"]);
+        params.exec_id = None;
+
+        assert!(matches!(
+            exec::exec_config(&allowlist(), &params),
+            Err(DockerApiError::InvalidExec {
+                reason: "exec ID is empty"
+            })
+        ));
+    }
+
+    #[test]
+    fn config_requires_command() {
+        assert!(matches!(
+            exec::exec_config(&allowlist(), &params(&[])),
+            Err(DockerApiError::InvalidExec {
+                reason: "command is empty"
+            })
+        ));
+    }
+}
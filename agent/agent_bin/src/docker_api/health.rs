@@ -0,0 +1,143 @@
+use agent_wire::deploything::v1::HealthCheck;
+use bollard::secret::HealthConfig;
+use prost_types::Duration;
+
+use crate::docker_api::errors::DockerApiError;
+
+/// Docker rejects non-zero durations shorter than this.
+const MIN_DURATION_NS: i64 = 1_000_000;
+
+/// Converts a requested health check into Docker's health config.
+pub fn health_config(
+    health_check: Option<&HealthCheck>,
+) -> Result<Option<HealthConfig>, DockerApiError> {
+    let Some(health_check) = health_check else {
+        return Ok(None);
+    };
+
+    if health_check.cmd.is_empty() {
+        return Err(DockerApiError::InvalidHealthCheck {
+            reason: "command is empty",
+        });
+    }
+
+    if health_check.retries.is_some_and(|retries| retries < 0) {
+        return Err(DockerApiError::InvalidHealthCheck {
+            reason: "retries must not be negative",
+        });
+    }
+
+    let test = ["CMD".to_string()]
+        .into_iter()
+        .chain(health_check.cmd.iter().cloned())
+        .collect();
+
+    Ok(Some(HealthConfig {
+        test: Some(test),
+        interval: nanos(health_check.interval.as_ref())?,
+        timeout: nanos(health_check.timeout.as_ref())?,
+        retries: health_check.retries,
+        start_period: nanos(health_check.start_period.as_ref())?,
+        ..Default::default()
+    }))
+}
+
+fn nanos(duration: Option<&Duration>) -> Result<Option<i64>, DockerApiError> {
+    let Some(duration) = duration else {
+        return Ok(None);
+    };
+
+    let nanos = duration
+        .seconds
+        .checked_mul(1_000_000_000)
+        .and_then(|ns| ns.checked_add(duration.nanos as i64))
+        .ok_or(DockerApiError::InvalidHealthCheck {
+            reason: "duration is too long",
+        })?;
+
+    if nanos < MIN_DURATION_NS {
+        return Err(DockerApiError::InvalidHealthCheck {
+            reason: "durations must be at least 1ms",
+        });
+    }
+
+    Ok(Some(nanos))
+}
+
+#[cfg(test)]
+mod test {
+    use agent_wire::deploything::v1::HealthCheck;
+    use prost_types::Duration;
+
+    use crate::docker_api::{errors::DockerApiError, health};
+
+    fn check(cmd: &[&str]) -> HealthCheck {
+        HealthCheck {
+            cmd: cmd.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn no_health_check() {
+        assert!(health::health_config(None).unwrap().is_none());
+    }
+
+    #[test]
+    fn health_check_runs_cmd_directly() {
+        let mut health_check = check(&["curl", "-f", "http://localhost/"]);
+        health_check.interval = Some(Duration {
+            seconds: 5,
+            nanos: 0,
+        });
+        health_check.timeout = Some(Duration {
+            seconds: 0,
+            nanos: 500_000_000,
+        });
+        health_check.retries = Some(3);
+
+        let config = health::health_config(Some(&health_check)).unwrap().unwrap();
+
+        assert_eq!(
+            config.test.unwrap(),
+            vec!["CMD", "curl", "-f", "http://localhost/"]
+        );
+        assert_eq!(config.interval, Some(5_000_000_000));
+        assert_eq!(config.timeout, Some(500_000_000));
+        assert_eq!(config.retries, Some(3));
+        assert_eq!(config.start_period, None);
+    }
+
+    #[test]
+    fn health_check_requires_cmd() {
+        assert!(matches!(
+            health::health_config(Some(&check(&[]))),
+            Err(DockerApiError::InvalidHealthCheck { .. })
+        ));
+    }
+
+    #[test]
+    fn health_check_rejects_short_durations() {
+        let mut health_check = check(&["true"]);
+        health_check.start_period = Some(Duration {
+            seconds: 0,
+            nanos: 1_000,
+        });
+
+        assert!(matches!(
+            health::health_config(Some(&health_check)),
+            Err(DockerApiError::InvalidHealthCheck { .. })
+        ));
+    }
+
+    #[test]
+    fn health_check_rejects_negative_retries() {
+        let mut health_check = check(&["true"]);
+        health_check.retries = Some(-1);
+
+        assert!(matches!(
+            health::health_config(Some(&health_check)),
+            Err(DockerApiError::InvalidHealthCheck { .. })
+        ));
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use agent_wire::deploything::v1::HostInfo;
+use bollard::Docker;
+use tracing::{instrument, warn};
+
+/// Gathers facts about the host from `/proc` and Docker's info API. Facts that cannot be
+/// gathered are left unset rather than failing the snapshot.
+#[instrument(skip(docker))]
+pub async fn info(docker: &Docker, agent_uptime: Duration) -> HostInfo {
+    let mut host = HostInfo {
+        hostname: read_proc("sys/kernel/hostname").map(|s| s.trim().to_string()),
+        kernel_version: read_proc("sys/kernel/osrelease").map(|s| s.trim().to_string()),
+        agent_uptime: prost_types::Duration::try_from(agent_uptime).ok(),
+        ..Default::default()
+    };
+
+    if let Some((total, available)) = read_proc("meminfo").as_deref().and_then(parse_meminfo) {
+        host.memory_total_bytes = Some(total);
+        host.memory_available_bytes = Some(available);
+    }
+
+    if let Some((one, five, fifteen)) = read_proc("loadavg").as_deref().and_then(parse_loadavg) {
+        host.load_average_1m = Some(one);
+        host.load_average_5m = Some(five);
+        host.load_average_15m = Some(fifteen);
+    }
+
+    match docker.info().await {
+        Ok(info) => {
+            host.hostname = host.hostname.or(info.name);
+            host.kernel_version = host.kernel_version.or(info.kernel_version);
+            host.operating_system = info.operating_system;
+            host.architecture = info.architecture;
+            host.cpu_count = info.ncpu.and_then(|n| u32::try_from(n).ok());
+            host.memory_total_bytes = host
+                .memory_total_bytes
+                .or(info.mem_total.and_then(|m| u64::try_from(m).ok()));
+            host.docker_root_dir = info.docker_root_dir;
+            host.docker_version = info.server_version;
+        }
+        Err(e) => warn!("Docker info failed: {e}"),
+    }
+
+    if host.cpu_count.is_none() {
+        host.cpu_count = std::thread::available_parallelism()
+            .ok()
+            .and_then(|n| u32::try_from(n.get()).ok());
+    }
+
+    // Only meaningful when the agent shares the host's filesystem.
+    if let Some(root) = &host.docker_root_dir {
+        match rustix::fs::statvfs(root.as_str()) {
+            Ok(stat) => {
+                host.disk_total_bytes = Some(stat.f_blocks * stat.f_frsize);
+                host.disk_available_bytes = Some(stat.f_bavail * stat.f_frsize);
+            }
+            Err(e) => warn!("Failed to stat Docker root {root}: {e}"),
+        }
+    }
+
+    host
+}
+
+fn read_proc(path: &str) -> Option<String> {
+    std::fs::read_to_string(format!("/proc/{path}"))
+        .inspect_err(|e| warn!("Failed to read /proc/{path}: {e}"))
+        .ok()
+}
+
+/// Returns the total and available memory, in bytes.
+fn parse_meminfo(meminfo: &str) -> Option<(u64, u64)> {
+    let field = |name: &str| {
+        meminfo.lines().find_map(|line| {
+            let kib = line.strip_prefix(name)?.strip_prefix(':')?;
+            let kib = kib.trim().strip_suffix("kB")?.trim().parse::<u64>().ok()?;
+            Some(kib * 1024)
+        })
+    };
+
+    Some((field("MemTotal")?, field("MemAvailable")?))
+}
+
+/// Returns the 1, 5 and 15 minute load averages.
+fn parse_loadavg(loadavg: &str) -> Option<(f64, f64, f64)> {
+    let mut fields = loadavg.split_whitespace().map(|f| f.parse::<f64>().ok());
+    Some((fields.next()??, fields.next()??, fields.next()??))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::docker_api::host;
+
+    #[test]
+    fn parse_meminfo() {
+        let meminfo = "MemTotal:       16303532 kB\n\
+                       MemFree:         1215100 kB\n\
+                       MemAvailable:    9817168 kB\n\
+                       Buffers:          712832 kB\n";
+
+        assert_eq!(
+            host::parse_meminfo(meminfo),
+            Some((16_303_532 * 1024, 9_817_168 * 1024))
+        );
+    }
+
+    #[test]
+    fn parse_meminfo_without_available() {
+        assert_eq!(host::parse_meminfo("MemTotal:       16303532 kB\n"), None);
+    }
+
+    #[test]
+    fn parse_loadavg() {
+        assert_eq!(
+            host::parse_loadavg("0.52 0.58 0.59 1/1187 254314\n"),
+            Some((0.52, 0.58, 0.59))
+        );
+        assert_eq!(host::parse_loadavg("0.52"), None);
+    }
+}
@@ -1,6 +1,13 @@
-use bollard::{Docker, query_parameters::CreateImageOptionsBuilder};
+use std::collections::HashMap;
+
+use bollard::{
+    Docker, body_full,
+    query_parameters::{BuildImageOptionsBuilder, CreateImageOptionsBuilder},
+};
+use bytes::Bytes;
+use tokio::sync::mpsc::Sender;
 use tokio_stream::StreamExt;
-use tracing::{error, info, instrument};
+use tracing::{error, info, instrument, warn};
 
 use crate::docker_api::errors::DockerApiError;
 
@@ -10,6 +17,15 @@ pub struct ImageRef {
     tag: String,
 }
 
+impl ImageRef {
+    pub fn new(name: &str, tag: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            tag: tag.to_string(),
+        }
+    }
+}
+
 impl std::fmt::Display for ImageRef {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}:{}", self.name, self.tag)
@@ -38,8 +54,66 @@ pub async fn pull(docker: &Docker, name: &str, tag: &str) -> Result<ImageRef, Do
 
     info!("Pull complete");
 
-    Ok(ImageRef {
-        name: name.to_string(),
-        tag: tag.to_string(),
-    })
+    Ok(ImageRef::new(name, tag))
+}
+
+/// Returns whether the image is present in the local image store.
+#[instrument(skip(docker))]
+pub async fn exists(docker: &Docker, image_ref: &ImageRef) -> bool {
+    docker.inspect_image(&image_ref.to_string()).await.is_ok()
+}
+
+/// Builds an image from a tar build context and tags it with `image_ref`.
+/// Every line of build output is forwarded to `log_tx` as it arrives.
+#[instrument(skip(docker, build_args, context, log_tx))]
+pub async fn build(
+    docker: &Docker,
+    image_ref: &ImageRef,
+    dockerfile: &str,
+    build_args: &HashMap<String, String>,
+    context: Bytes,
+    log_tx: Sender<String>,
+) -> Result<(), DockerApiError> {
+    info!("Building image");
+
+    let options = BuildImageOptionsBuilder::new()
+        .dockerfile(dockerfile)
+        .t(&image_ref.to_string())
+        .buildargs(build_args)
+        .rm(true)
+        .build();
+
+    let mut build_stream = docker.build_image(options, None, Some(body_full(context)));
+
+    let build_failed = || DockerApiError::ImageBuildFailed {
+        image: image_ref.to_string(),
+    };
+
+    while let Some(info) = build_stream.next().await {
+        let info = match info {
+            Ok(info) => info,
+            Err(e) => {
+                error!("Build failed: {e}");
+                let _ = log_tx.send(e.to_string()).await;
+                return Err(build_failed());
+            }
+        };
+
+        if let Some(line) = info.stream.or(info.status) {
+            let line = line.trim_end();
+            if !line.is_empty() && log_tx.send(line.to_string()).await.is_err() {
+                warn!("Build log receiver dropped");
+            }
+        }
+
+        if let Some(e) = info.error {
+            error!("Build failed: {e}");
+            let _ = log_tx.send(e).await;
+            return Err(build_failed());
+        }
+    }
+
+    info!("Build complete");
+
+    Ok(())
 }
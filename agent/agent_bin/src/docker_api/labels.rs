@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use agent_wire::deploything::v1::RunParams;
+
+use crate::docker_api::errors::DockerApiError;
+
+/// Labels under this prefix are reserved for the agent.
+pub const RESERVED_PREFIX: &str = "deployth.ing/";
+
+/// Marks containers and volumes that were created by, and are therefore managed by, the agent.
+pub const MANAGED: &str = "deployth.ing/managed";
+
+/// The application a container belongs to.
+pub const APP: &str = "deployth.ing/app";
+
+/// The deployment, or revision, of the application a container belongs to.
+pub const DEPLOYMENT: &str = "deployth.ing/deployment";
+
+/// The revision of the desired state a reconciled container was created from.
+pub const REVISION: &str = "deployth.ing/revision";
+
+/// Which of its app's replicas a reconciled container is.
+pub const REPLICA: &str = "deployth.ing/replica";
+
+/// Identifies a container created by reconciling towards a desired state.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Replica {
+    pub app_name: String,
+    pub revision: String,
+    pub index: u32,
+}
+
+impl Replica {
+    /// Returns `None` for containers that were not created by reconciliation.
+    pub fn from_labels(labels: &HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            app_name: labels.get(APP)?.clone(),
+            revision: labels.get(REVISION)?.clone(),
+            index: labels.get(REPLICA)?.parse().ok()?,
+        })
+    }
+
+    fn labels(&self) -> [(String, String); 3] {
+        [
+            (APP.to_string(), self.app_name.clone()),
+            (REVISION.to_string(), self.revision.clone()),
+            (REPLICA.to_string(), self.index.to_string()),
+        ]
+    }
+}
+
+/// Labels attached to every object the agent creates.
+pub fn managed() -> HashMap<String, String> {
+    HashMap::from([(MANAGED.to_string(), "true".to_string())])
+}
+
+/// Labels attached to a container created from `params`, and `replica` if it is being
+/// created by reconciliation.
+pub fn for_container(
+    params: &RunParams,
+    replica: Option<&Replica>,
+) -> Result<HashMap<String, String>, DockerApiError> {
+    if let Some(key) = params
+        .labels
+        .keys()
+        .find(|key| key.starts_with(RESERVED_PREFIX))
+    {
+        return Err(DockerApiError::ReservedLabel { key: key.clone() });
+    }
+
+    let mut labels = params.labels.clone();
+    labels.extend(managed());
+
+    if let Some(app_name) = &params.app_name {
+        labels.insert(APP.to_string(), app_name.clone());
+    }
+
+    if let Some(deployment_id) = &params.deployment_id {
+        labels.insert(DEPLOYMENT.to_string(), deployment_id.clone());
+    }
+
+    if let Some(replica) = replica {
+        labels.extend(replica.labels());
+    }
+
+    Ok(labels)
+}
+
+pub fn is_managed(labels: &HashMap<String, String>) -> bool {
+    labels.get(MANAGED).is_some_and(|v| v == "true")
+}
+
+/// A Docker `label` filter matching managed objects.
+pub fn managed_filter() -> HashMap<&'static str, Vec<String>> {
+    HashMap::from([("label", vec![format!("{MANAGED}=true")])])
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use agent_wire::deploything::v1::RunParams;
+
+    use crate::docker_api::{errors::DockerApiError, labels};
+
+    #[test]
+    fn container_labels_include_user_and_identity_labels() {
+        let params = RunParams {
+            app_name: Some("web".into()),
+            labels: HashMap::from([("team".to_string(), "payments".to_string())]),
+            ..Default::default()
+        };
+
+        let container_labels = labels::for_container(&params, None).unwrap();
+
+        assert_eq!("payments", container_labels["team"]);
+        assert_eq!("web", container_labels[labels::APP]);
+        assert!(labels::is_managed(&container_labels));
+        assert!(!container_labels.contains_key(labels::DEPLOYMENT));
+    }
+
+    #[test]
+    fn replica_labels_round_trip() {
+        let params = RunParams {
+            app_name: Some("web".into()),
+            ..Default::default()
+        };
+        let replica = labels::Replica {
+            app_name: "web".to_string(),
+            revision: "0a1b2c3d".to_string(),
+            index: 2,
+        };
+
+        let container_labels = labels::for_container(&params, Some(&replica)).unwrap();
+
+        assert_eq!(
+            labels::Replica::from_labels(&container_labels),
+            Some(replica)
+        );
+        assert_eq!(
+            labels::Replica::from_labels(&labels::for_container(&params, None).unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn reserved_labels_are_rejected() {
+        let params = RunParams {
+            labels: HashMap::from([(labels::MANAGED.to_string(), "false".to_string())]),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            labels::for_container(&params, None),
+            Err(DockerApiError::ReservedLabel { .. })
+        ));
+    }
+}
@@ -0,0 +1,133 @@
+use agent_wire::deploything::v1::{LogStream, SubscribeLogsParams};
+use bollard::{Docker, container::LogOutput, query_parameters::LogsOptionsBuilder};
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use tracing::error;
+
+use crate::docker_api::errors::DockerApiError;
+
+/// Streams a container's output as it is read from Docker.
+pub fn stream(
+    docker: &Docker,
+    params: &SubscribeLogsParams,
+) -> Result<impl Stream<Item = Result<(LogStream, Bytes), DockerApiError>> + use<>, DockerApiError>
+{
+    let container_id = params.container_id().to_string();
+    let options = logs_options(params)?;
+
+    let stream = docker
+        .logs(&container_id, Some(options))
+        .map(move |output| match output {
+            Ok(LogOutput::StdOut { message } | LogOutput::Console { message }) => {
+                Ok((LogStream::Stdout, message))
+            }
+            Ok(LogOutput::StdErr { message }) => Ok((LogStream::Stderr, message)),
+            Ok(LogOutput::StdIn { message }) => Ok((LogStream::Unspecified, message)),
+            Err(e) => {
+                error!("Container logs failed: {e}");
+                Err(DockerApiError::ContainerLogsFailed {
+                    container_id: container_id.clone(),
+                })
+            }
+        });
+
+    Ok(stream)
+}
+
+fn logs_options(
+    params: &SubscribeLogsParams,
+) -> Result<bollard::query_parameters::LogsOptions, DockerApiError> {
+    if params.container_id().is_empty() {
+        return Err(DockerApiError::InvalidLogSubscription {
+            reason: "container ID is empty",
+        });
+    }
+
+    let stdout = params.stdout.unwrap_or(true);
+    let stderr = params.stderr.unwrap_or(true);
+    if !stdout && !stderr {
+        return Err(DockerApiError::InvalidLogSubscription {
+            reason: "neither stdout nor stderr was selected",
+        });
+    }
+
+    let since = match &params.since {
+        Some(since) => {
+            i32::try_from(since.seconds).map_err(|_| DockerApiError::InvalidLogSubscription {
+                reason: "since is out of range",
+            })?
+        }
+        None => 0,
+    };
+
+    let tail = params
+        .tail
+        .map_or("all".to_string(), |tail| tail.to_string());
+
+    Ok(LogsOptionsBuilder::new()
+        .follow(params.follow())
+        .stdout(stdout)
+        .stderr(stderr)
+        .since(since)
+        .tail(&tail)
+        .build())
+}
+
+#[cfg(test)]
+mod test {
+    use agent_wire::deploything::v1::SubscribeLogsParams;
+    use prost_types::Timestamp;
+
+    use crate::docker_api::{errors::DockerApiError, logs};
+
+    fn params() -> SubscribeLogsParams {
+        SubscribeLogsParams {
+            subscription_id: Some("sub".to_string()),
+            container_id: Some("abc123".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn defaults_to_both_streams_and_all_lines() {
+        let options = logs::logs_options(&params()).unwrap();
+
+        assert!(options.stdout);
+        assert!(options.stderr);
+        assert!(!options.follow);
+        assert_eq!(options.tail, "all");
+        assert_eq!(options.since, 0);
+    }
+
+    #[test]
+    fn tail_and_since() {
+        let mut params = params();
+        params.follow = Some(true);
+        params.tail = Some(100);
+        params.stderr = Some(false);
+        params.since = Some(Timestamp {
+            seconds: 1_714_566_600,
+            nanos: 0,
+        });
+
+        let options = logs::logs_options(&params).unwrap();
+
+        assert!(options.follow);
+        assert!(options.stdout);
+        assert!(!options.stderr);
+        assert_eq!(options.tail, "100");
+        assert_eq!(options.since, 1_714_566_600);
+    }
+
+    #[test]
+    fn requires_a_stream() {
+        let mut params = params();
+        params.stdout = Some(false);
+        params.stderr = Some(false);
+
+        assert!(matches!(
+            logs::logs_options(&params),
+            Err(DockerApiError::InvalidLogSubscription { .. })
+        ));
+    }
+}
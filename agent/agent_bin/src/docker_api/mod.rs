@@ -1,59 +1,356 @@
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
-use agent_wire::deploything::v1::{AgentSnapshot, ContainerHostConfig};
+use agent_wire::deploything::v1::{
+    AgentSnapshot, BuildParams, ContainerStatus, ExecParams, HostInfo, LogStream, PublishedPort,
+    PullPolicy, RunParams, SubscribeLogsParams, Volume,
+};
 use bollard::Docker;
+use bytes::Bytes;
+use futures_util::Stream;
 use prost_types::Timestamp;
-use tracing::instrument;
+use tokio::sync::mpsc::Sender;
+use tracing::{instrument, warn};
 
-use crate::docker_api::errors::DockerApiError;
+use crate::docker_api::image::ImageRef;
 
+mod archive;
 mod container;
 mod errors;
 mod events;
+mod exec;
+mod health;
+mod host;
 mod image;
+mod labels;
+mod logs;
+mod mounts;
+mod network;
+mod ports;
+mod resources;
+mod stats;
+mod volume;
 
+pub use container::{InspectCache, StopOptions, parse_stop_signal};
+pub use errors::DockerApiError;
 pub use events::DockerEventsHandler;
+pub use exec::{Exec, ExecAllowlist};
+pub use labels::Replica;
+pub use mounts::{BindAllowlist, parse_bind_prefix};
+pub use resources::{ResourceMaximums, parse_max_cpus, parse_maximum};
+pub use stats::{StatsCache, StatsCollector};
 
 pub struct Container<'a> {
     docker: &'a Docker,
     id: String,
+    ports: Vec<PublishedPort>,
 }
 
 impl<'a> Container<'a> {
     #[instrument(skip(docker))]
     pub async fn spawn_from_image(
         docker: &'a Docker,
-        image_name: &str,
-        tag: &str,
-        host_config: Option<&ContainerHostConfig>,
+        params: &RunParams,
     ) -> Result<Self, DockerApiError> {
-        let image_ref = image::pull(docker, image_name, tag).await?;
+        Self::spawn(docker, params, None).await
+    }
+
+    /// Spawns a container as `replica` of an app, so that it can be recognised when
+    /// reconciling towards a desired state.
+    #[instrument(skip(docker))]
+    pub async fn spawn_replica(
+        docker: &'a Docker,
+        params: &RunParams,
+        replica: &Replica,
+    ) -> Result<Self, DockerApiError> {
+        Self::spawn(docker, params, Some(replica)).await
+    }
+
+    async fn spawn(
+        docker: &'a Docker,
+        params: &RunParams,
+        replica: Option<&Replica>,
+    ) -> Result<Self, DockerApiError> {
+        let image_ref = resolve_image(docker, params).await?;
 
-        let id = container::create(docker, &image_ref, host_config).await?;
-        container::start(docker, &id).await?;
+        if let Some(host_config) = &params.container_host_config {
+            for name in mounts::volume_names(&host_config.mounts) {
+                volume::ensure(docker, name).await?;
+            }
+        }
 
-        let container = Self { docker, id };
+        for attachment in &params.networks {
+            network::ensure_managed(docker, attachment.network()).await?;
+        }
+
+        let id = container::create(docker, &image_ref, params, replica).await?;
+
+        // Nothing tracks a container that failed part way through starting, so it is
+        // removed rather than left behind.
+        let ports = match Self::start(docker, &id).await {
+            Ok(ports) => ports,
+            Err(e) => {
+                if container::remove(docker, &id, true, true).await.is_err() {
+                    warn!("Failed to remove container {id} after it failed to start");
+                }
+                return Err(e);
+            }
+        };
+
+        let container = Self { docker, id, ports };
 
         Ok(container)
     }
 
+    async fn start(docker: &Docker, id: &str) -> Result<Vec<PublishedPort>, DockerApiError> {
+        container::start(docker, id).await?;
+        container::published_ports(docker, id).await
+    }
+
     #[instrument(skip(self))]
-    pub async fn stop(&self) -> Result<(), DockerApiError> {
-        container::stop(self.docker, &self.id).await
+    pub async fn stop(&self, options: &StopOptions) -> Result<(), DockerApiError> {
+        container::stop(self.docker, &self.id, options).await
     }
 
     pub fn id(&self) -> &str {
         &self.id
     }
+
+    /// The host ports the container was published on when it started.
+    pub fn ports(&self) -> &[PublishedPort] {
+        &self.ports
+    }
+}
+
+/// Makes sure the image requested by `params` is available locally, pulling it if the
+/// pull policy allows.
+async fn resolve_image(docker: &Docker, params: &RunParams) -> Result<ImageRef, DockerApiError> {
+    let (name, tag) = (params.image_name(), params.tag());
+
+    match params.pull_policy() {
+        PullPolicy::Unspecified | PullPolicy::Always => image::pull(docker, name, tag).await,
+        PullPolicy::IfNotPresent => {
+            let image_ref = ImageRef::new(name, tag);
+            if image::exists(docker, &image_ref).await {
+                Ok(image_ref)
+            } else {
+                image::pull(docker, name, tag).await
+            }
+        }
+        PullPolicy::Never => {
+            let image_ref = ImageRef::new(name, tag);
+            if image::exists(docker, &image_ref).await {
+                Ok(image_ref)
+            } else {
+                Err(DockerApiError::ImageNotPresent {
+                    image: image_ref.to_string(),
+                })
+            }
+        }
+    }
+}
+
+/// Builds the image described by `params` from a tar build context, streaming build
+/// output to `log_tx`.
+#[instrument(skip(docker, context, log_tx))]
+pub async fn build_image(
+    docker: &Docker,
+    params: &BuildParams,
+    context: Bytes,
+    log_tx: Sender<String>,
+) -> Result<(), DockerApiError> {
+    let image_ref = ImageRef::new(params.image_name(), params.tag());
+    let dockerfile = params.dockerfile.as_deref().unwrap_or("Dockerfile");
+
+    image::build(
+        docker,
+        &image_ref,
+        dockerfile,
+        &params.build_args,
+        context,
+        log_tx,
+    )
+    .await
+}
+
+/// Fails unless `app_name` can be used to name an app's containers.
+pub fn validate_app_name(app_name: &str) -> Result<(), DockerApiError> {
+    container::validate_name_part(app_name)
+}
+
+/// Lists the containers created by the agent, whatever their state.
+#[instrument(skip(docker, inspected))]
+pub async fn list_containers(
+    docker: &Docker,
+    inspected: &mut InspectCache,
+) -> Result<Vec<ContainerStatus>, DockerApiError> {
+    container::list(docker, false, inspected).await
+}
+
+/// Stops a managed container.
+#[instrument(skip(docker))]
+pub async fn stop_container(
+    docker: &Docker,
+    container_id: &str,
+    options: &StopOptions,
+) -> Result<(), DockerApiError> {
+    container::ensure_managed(docker, container_id).await?;
+    container::stop(docker, container_id, options).await
+}
+
+/// Removes a managed container, optionally along with its anonymous volumes.
+#[instrument(skip(docker))]
+pub async fn remove_container(
+    docker: &Docker,
+    container_id: &str,
+    remove_volumes: bool,
+    force: bool,
+) -> Result<(), DockerApiError> {
+    container::ensure_managed(docker, container_id).await?;
+    container::remove(docker, container_id, remove_volumes, force).await
+}
+
+/// Stops and starts a managed container again.
+#[instrument(skip(docker))]
+pub async fn restart_container(
+    docker: &Docker,
+    container_id: &str,
+    options: &StopOptions,
+) -> Result<(), DockerApiError> {
+    container::ensure_managed(docker, container_id).await?;
+    container::restart(docker, container_id, options).await
 }
 
+/// Suspends the processes of a managed container.
 #[instrument(skip(docker))]
-pub async fn build_snapshot(docker: &Docker) -> Result<AgentSnapshot, DockerApiError> {
-    let container_status = container::list(docker).await?;
+pub async fn pause_container(docker: &Docker, container_id: &str) -> Result<(), DockerApiError> {
+    container::ensure_managed(docker, container_id).await?;
+    container::pause(docker, container_id).await
+}
+
+/// Resumes the processes of a paused managed container.
+#[instrument(skip(docker))]
+pub async fn unpause_container(docker: &Docker, container_id: &str) -> Result<(), DockerApiError> {
+    container::ensure_managed(docker, container_id).await?;
+    container::unpause(docker, container_id).await
+}
+
+/// Lists the volumes created by the agent.
+#[instrument(skip(docker))]
+pub async fn list_volumes(docker: &Docker) -> Result<Vec<Volume>, DockerApiError> {
+    volume::list(docker).await
+}
+
+/// Removes a volume created by the agent.
+#[instrument(skip(docker))]
+pub async fn remove_volume(docker: &Docker, name: &str, force: bool) -> Result<(), DockerApiError> {
+    volume::remove(docker, name, force).await
+}
+
+/// Creates a managed network, returning its ID.
+#[instrument(skip(docker))]
+pub async fn create_network(
+    docker: &Docker,
+    name: &str,
+    internal: bool,
+) -> Result<String, DockerApiError> {
+    network::create(docker, name, internal).await
+}
+
+/// Removes a network created by the agent.
+#[instrument(skip(docker))]
+pub async fn remove_network(docker: &Docker, name: &str) -> Result<(), DockerApiError> {
+    network::remove(docker, name).await
+}
+
+/// Streams the output of a managed container.
+#[instrument(skip(docker))]
+pub async fn container_logs(
+    docker: &Docker,
+    params: &SubscribeLogsParams,
+) -> Result<impl Stream<Item = Result<(LogStream, Bytes), DockerApiError>> + use<>, DockerApiError>
+{
+    let stream = logs::stream(docker, params)?;
+    container::ensure_managed(docker, params.container_id()).await?;
+    Ok(stream)
+}
+
+/// Checks that an archive can be uploaded to `path` in a managed container, so that a bad
+/// upload is rejected before the archive is sent.
+#[instrument(skip(docker))]
+pub async fn check_archive_target(
+    docker: &Docker,
+    container_id: &str,
+    path: &str,
+) -> Result<(), DockerApiError> {
+    archive::validate_path(path)?;
+    container::ensure_managed(docker, container_id).await
+}
+
+/// Extracts a tar archive into `path`, which must be an existing directory, in a managed
+/// container.
+#[instrument(skip(docker, archive))]
+pub async fn upload_archive(
+    docker: &Docker,
+    container_id: &str,
+    path: &str,
+    archive: Bytes,
+) -> Result<(), DockerApiError> {
+    check_archive_target(docker, container_id, path).await?;
+    archive::upload(docker, container_id, path, archive).await
+}
+
+/// Streams `path` in a managed container as a tar archive.
+#[instrument(skip(docker))]
+pub async fn download_archive(
+    docker: &Docker,
+    container_id: &str,
+    path: &str,
+) -> Result<impl Stream<Item = Result<Bytes, DockerApiError>> + use<>, DockerApiError> {
+    check_archive_target(docker, container_id, path).await?;
+    Ok(archive::download(docker, container_id, path))
+}
+
+/// Starts a process inside a managed container. The process's output must be drained for
+/// it to make progress.
+#[instrument(skip(docker, allowlist))]
+pub async fn start_exec(
+    docker: &Docker,
+    allowlist: &ExecAllowlist,
+    params: &ExecParams,
+) -> Result<Exec, DockerApiError> {
+    let config = exec::exec_config(allowlist, params)?;
+    container::ensure_managed(docker, params.container_id()).await?;
+    exec::start(docker, params.container_id(), config).await
+}
+
+/// Returns the exit code of an exec's process, or `None` if it is still running.
+pub async fn exec_exit_code(docker: &Docker, exec_id: &str) -> Result<Option<i64>, DockerApiError> {
+    exec::exit_code(docker, exec_id).await
+}
+
+/// Gathers facts about the host for placement decisions.
+pub async fn host_info(docker: &Docker, agent_uptime: Duration) -> HostInfo {
+    host::info(docker, agent_uptime).await
+}
+
+#[instrument(skip(docker, stats, inspected))]
+pub async fn build_snapshot(
+    docker: &Docker,
+    include_unmanaged: bool,
+    stats: &StatsCache,
+    inspected: &mut InspectCache,
+) -> Result<AgentSnapshot, DockerApiError> {
+    let mut container_status = container::list(docker, include_unmanaged, inspected).await?;
+
+    for status in &mut container_status {
+        status.stats = status.id.as_deref().and_then(|id| stats.get(id));
+    }
 
     let snapshot = AgentSnapshot {
         container_status,
         timestamp: Some(Timestamp::from(SystemTime::now())),
+        sequence: None,
+        host: None,
     };
 
     Ok(snapshot)
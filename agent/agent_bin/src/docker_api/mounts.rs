@@ -0,0 +1,316 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use agent_wire::deploything::v1::{Mount, mount::Source};
+use bollard::secret::{Mount as DockerMount, MountTypeEnum};
+
+use crate::docker_api::errors::DockerApiError;
+
+/// The host directories that containers may bind-mount. An empty allowlist disables bind
+/// mounts, since a bind mount can hand a container anything on the host, including the
+/// Docker socket.
+#[derive(Debug, Default, Clone)]
+pub struct BindAllowlist {
+    prefixes: Vec<PathBuf>,
+}
+
+impl BindAllowlist {
+    /// `prefixes` must already be resolved, as `parse_bind_prefix` does.
+    pub fn new(prefixes: impl IntoIterator<Item = PathBuf>) -> Self {
+        Self {
+            prefixes: prefixes.into_iter().collect(),
+        }
+    }
+
+    /// Resolves the host path of each bind mount and checks it is within an allowed
+    /// directory, replacing it with the resolved path so Docker mounts what was checked.
+    /// Symlinks are followed because a container that can write beneath an allowed
+    /// directory could otherwise link from there to anywhere on the host. Paths are
+    /// matched by whole components, so allowing `/srv/data` does not allow `/srv/database`.
+    pub fn resolve(&self, mounts: &mut [Mount]) -> Result<(), DockerApiError> {
+        for mount in mounts {
+            let Some(Source::HostPath(path)) = &mut mount.source else {
+                continue;
+            };
+
+            let resolved = is_normalised(path)
+                .then(|| fs::canonicalize(&*path).ok())
+                .flatten()
+                .filter(|resolved| {
+                    self.prefixes
+                        .iter()
+                        .any(|prefix| resolved.starts_with(prefix))
+                })
+                .and_then(|resolved| resolved.into_os_string().into_string().ok());
+
+            match resolved {
+                Some(resolved) => *path = resolved,
+                None => return Err(DockerApiError::BindMountNotAllowed { path: path.clone() }),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a `--bind-allow` prefix, which must be written the same way as the host paths
+/// it is matched against. The directory must exist, and is resolved once at startup so
+/// that it compares equal to resolved host paths.
+pub fn parse_bind_prefix(prefix: &str) -> Result<PathBuf, String> {
+    if !is_normalised(prefix) {
+        return Err(
+            "must be an absolute path without '.', '..' or repeated or trailing '/'".into(),
+        );
+    }
+
+    fs::canonicalize(prefix).map_err(|e| format!("cannot be resolved: {e}"))
+}
+
+/// Whether `path` is absolute and has no `.` or `..` components, empty components or
+/// trailing `/`, so that it reads the same as the path Docker will mount.
+fn is_normalised(path: &str) -> bool {
+    let Some(relative) = path.strip_prefix('/') else {
+        return false;
+    };
+
+    relative.is_empty()
+        || relative
+            .split('/')
+            .all(|component| !matches!(component, "" | "." | ".."))
+}
+
+/// Validates `mounts` and converts them into Docker mounts.
+pub fn docker_mounts(mounts: &[Mount]) -> Result<Vec<DockerMount>, DockerApiError> {
+    mounts.iter().map(docker_mount).collect()
+}
+
+/// The names of the volumes referenced by `mounts`.
+pub fn volume_names(mounts: &[Mount]) -> impl Iterator<Item = &str> {
+    mounts.iter().filter_map(|mount| match &mount.source {
+        Some(Source::Volume(name)) => Some(name.as_str()),
+        _ => None,
+    })
+}
+
+fn docker_mount(mount: &Mount) -> Result<DockerMount, DockerApiError> {
+    let invalid = |reason| DockerApiError::InvalidMount {
+        target: mount.target().to_string(),
+        reason,
+    };
+
+    if !Path::new(mount.target()).is_absolute() {
+        return Err(invalid("target must be an absolute path"));
+    }
+
+    let (typ, source) = match &mount.source {
+        Some(Source::Volume(name)) if !name.is_empty() && !name.contains('/') => {
+            (MountTypeEnum::VOLUME, name)
+        }
+        Some(Source::Volume(_)) => return Err(invalid("volume name is invalid")),
+        Some(Source::HostPath(path)) if is_normalised(path) => (MountTypeEnum::BIND, path),
+        Some(Source::HostPath(_)) => {
+            return Err(invalid("host path must be an absolute, normalised path"));
+        }
+        None => return Err(invalid("either a volume or a host path is required")),
+    };
+
+    Ok(DockerMount {
+        target: Some(mount.target().to_string()),
+        source: Some(source.clone()),
+        typ: Some(typ),
+        read_only: Some(mount.read_only()),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, os::unix::fs::symlink, path::PathBuf};
+
+    use agent_wire::deploything::v1::{Mount, mount::Source};
+    use bollard::secret::MountTypeEnum;
+    use tempfile::TempDir;
+
+    use crate::docker_api::{
+        errors::DockerApiError,
+        mounts::{BindAllowlist, docker_mounts, parse_bind_prefix},
+    };
+
+    fn mount(source: Option<Source>, target: &str) -> Mount {
+        Mount {
+            source,
+            target: Some(target.into()),
+            read_only: Some(true),
+        }
+    }
+
+    fn bind(path: &str) -> Mount {
+        mount(Some(Source::HostPath(path.into())), "/data")
+    }
+
+    #[test]
+    fn converts_volumes_and_bind_mounts() {
+        let mounts = docker_mounts(&[
+            mount(Some(Source::Volume("pgdata".into())), "/var/lib/postgresql"),
+            mount(Some(Source::HostPath("/etc/app".into())), "/config"),
+        ])
+        .unwrap();
+
+        assert_eq!(Some(MountTypeEnum::VOLUME), mounts[0].typ);
+        assert_eq!(Some("pgdata".to_string()), mounts[0].source);
+        assert_eq!(Some(MountTypeEnum::BIND), mounts[1].typ);
+        assert_eq!(Some(true), mounts[1].read_only);
+    }
+
+    #[test]
+    fn rejects_invalid_mounts() {
+        for m in [
+            mount(None, "/data"),
+            mount(Some(Source::Volume("data".into())), "data"),
+            mount(Some(Source::Volume("".into())), "/data"),
+            bind("relative/path"),
+            bind("/srv/app/../../etc"),
+            bind("/srv/./app"),
+            bind("/srv//app"),
+            bind("/srv/app/"),
+        ] {
+            assert!(
+                matches!(
+                    docker_mounts(std::slice::from_ref(&m)),
+                    Err(DockerApiError::InvalidMount { .. })
+                ),
+                "{m:?} should be rejected"
+            );
+        }
+    }
+
+    /// Creates `dirs` beneath a new temporary directory, allowing binds beneath `allowed`.
+    fn allowlist_in(dirs: &[&str], allowed: &str) -> (TempDir, BindAllowlist) {
+        let root = TempDir::new().unwrap();
+        for dir in dirs {
+            fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+
+        let prefix = parse_bind_prefix(root.path().join(allowed).to_str().unwrap()).unwrap();
+        (root, BindAllowlist::new([prefix]))
+    }
+
+    fn host_path(root: &TempDir, path: &str) -> String {
+        root.path().join(path).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn empty_bind_allowlist_disables_bind_mounts() {
+        let allowlist = BindAllowlist::default();
+
+        assert!(matches!(
+            allowlist.resolve(&mut [bind("/tmp")]),
+            Err(DockerApiError::BindMountNotAllowed { .. })
+        ));
+        assert!(
+            allowlist
+                .resolve(&mut [mount(Some(Source::Volume("data".into())), "/data")])
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn bind_allowlist_rejects_docker_socket() {
+        let (_root, allowlist) = allowlist_in(&["app"], "app");
+
+        assert!(matches!(
+            allowlist.resolve(&mut [bind("/var/run/docker.sock")]),
+            Err(DockerApiError::BindMountNotAllowed { .. })
+        ));
+    }
+
+    #[test]
+    fn bind_allowlist_matches_whole_components() {
+        let (root, allowlist) = allowlist_in(&["app/config", "application", "etc"], "app");
+
+        assert!(
+            allowlist
+                .resolve(&mut [bind(&host_path(&root, "app"))])
+                .is_ok()
+        );
+        assert!(
+            allowlist
+                .resolve(&mut [bind(&host_path(&root, "app/config"))])
+                .is_ok()
+        );
+        assert!(
+            allowlist
+                .resolve(&mut [bind(&host_path(&root, "application"))])
+                .is_err(),
+            "a sibling sharing the prefix's name should be rejected"
+        );
+        assert!(
+            allowlist
+                .resolve(&mut [bind(&host_path(&root, "app/../etc"))])
+                .is_err(),
+            "a path escaping the prefix should be rejected"
+        );
+        assert!(
+            allowlist
+                .resolve(&mut [bind(&host_path(&root, "app/missing"))])
+                .is_err(),
+            "a path that does not exist should be rejected"
+        );
+    }
+
+    #[test]
+    fn bind_allowlist_rejects_symlink_out_of_prefix() {
+        let (root, allowlist) = allowlist_in(&["app", "etc"], "app");
+        symlink(root.path().join("etc"), root.path().join("app/etc")).unwrap();
+        symlink("/", root.path().join("app/root")).unwrap();
+
+        for link in ["app/etc", "app/root"] {
+            assert!(
+                matches!(
+                    allowlist.resolve(&mut [bind(&host_path(&root, link))]),
+                    Err(DockerApiError::BindMountNotAllowed { .. })
+                ),
+                "{link:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn bind_allowlist_mounts_resolved_path() {
+        let (root, allowlist) = allowlist_in(&["app/config"], "app");
+        symlink(
+            root.path().join("app/config"),
+            root.path().join("app/current"),
+        )
+        .unwrap();
+
+        let mut mounts = [bind(&host_path(&root, "app/current"))];
+        allowlist.resolve(&mut mounts).unwrap();
+
+        let resolved = fs::canonicalize(root.path().join("app/config")).unwrap();
+        assert_eq!(
+            Some(Source::HostPath(resolved.to_str().unwrap().to_string())),
+            mounts[0].source
+        );
+    }
+
+    #[test]
+    fn bind_prefix_must_be_normalised_and_exist() {
+        assert_eq!(parse_bind_prefix("/"), Ok(PathBuf::from("/")));
+
+        for prefix in [
+            "",
+            "tmp",
+            "/tmp/",
+            "/tmp/../etc",
+            "/nonexistent/bind/prefix",
+        ] {
+            assert!(
+                parse_bind_prefix(prefix).is_err(),
+                "{prefix:?} should be rejected"
+            );
+        }
+    }
+}
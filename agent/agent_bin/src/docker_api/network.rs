@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use agent_wire::deploything::v1::NetworkAttachment;
+use bollard::{
+    Docker,
+    query_parameters::InspectNetworkOptions,
+    secret::{EndpointSettings, Network, NetworkCreateRequest, NetworkingConfig},
+};
+use tracing::{error, info, instrument};
+
+use crate::docker_api::{errors::DockerApiError, labels};
+
+/// Creates a managed bridge network, returning its ID.
+#[instrument(skip(docker))]
+pub async fn create(docker: &Docker, name: &str, internal: bool) -> Result<String, DockerApiError> {
+    info!("Creating network");
+
+    if name.is_empty() {
+        return Err(DockerApiError::InvalidNetwork {
+            name: name.to_string(),
+            reason: "name is empty",
+        });
+    }
+
+    let request = NetworkCreateRequest {
+        name: name.to_string(),
+        driver: Some("bridge".to_string()),
+        internal: Some(internal),
+        labels: Some(labels::managed()),
+        ..Default::default()
+    };
+
+    match docker.create_network(request).await {
+        Ok(res) => {
+            info!("Network create complete");
+            Ok(res.id)
+        }
+        Err(e) => {
+            error!("Network create failed: {e}");
+            Err(DockerApiError::NetworkCreateFailed {
+                name: name.to_string(),
+            })
+        }
+    }
+}
+
+/// Removes a managed network. Networks the agent did not create are never removed.
+#[instrument(skip(docker))]
+pub async fn remove(docker: &Docker, name: &str) -> Result<(), DockerApiError> {
+    let network = docker
+        .inspect_network(name, None::<InspectNetworkOptions>)
+        .await
+        .map_err(|e| {
+            error!("Network inspect failed: {e}");
+            DockerApiError::NetworkRemoveFailed {
+                name: name.to_string(),
+            }
+        })?;
+
+    check_managed(name, &network)?;
+
+    info!("Removing network");
+
+    match docker.remove_network(name).await {
+        Ok(_) => {
+            info!("Network removed");
+            Ok(())
+        }
+        Err(e) => {
+            error!("Network remove failed: {e}");
+            Err(DockerApiError::NetworkRemoveFailed {
+                name: name.to_string(),
+            })
+        }
+    }
+}
+
+/// Checks that containers may be attached to the named network. Only networks the agent
+/// created may be joined.
+#[instrument(skip(docker))]
+pub async fn ensure_managed(docker: &Docker, name: &str) -> Result<(), DockerApiError> {
+    let network = docker
+        .inspect_network(name, None::<InspectNetworkOptions>)
+        .await
+        .map_err(|e| {
+            error!("Network inspect failed: {e}");
+            DockerApiError::NetworkInspectFailed {
+                name: name.to_string(),
+            }
+        })?;
+
+    check_managed(name, &network)
+}
+
+fn check_managed(name: &str, network: &Network) -> Result<(), DockerApiError> {
+    if !network.labels.as_ref().is_some_and(labels::is_managed) {
+        return Err(DockerApiError::NetworkNotManaged {
+            name: name.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Builds the endpoint configuration that attaches a new container to `attachments`.
+/// Network modes that are not networks, such as `host` or another container's network
+/// namespace, are rejected since they would give the container access beyond its networks.
+pub fn networking_config(
+    attachments: &[NetworkAttachment],
+) -> Result<Option<NetworkingConfig>, DockerApiError> {
+    if attachments.is_empty() {
+        return Ok(None);
+    }
+
+    let mut endpoints = HashMap::with_capacity(attachments.len());
+
+    for attachment in attachments {
+        let name = attachment.network();
+        let invalid = |reason| DockerApiError::InvalidNetwork {
+            name: name.to_string(),
+            reason,
+        };
+
+        if name.is_empty() {
+            return Err(invalid("name is empty"));
+        }
+
+        if matches!(name, "host" | "none") || name.starts_with("container:") {
+            return Err(invalid("network mode is not a managed network"));
+        }
+
+        let settings = EndpointSettings {
+            aliases: (!attachment.aliases.is_empty()).then(|| attachment.aliases.clone()),
+            ..Default::default()
+        };
+
+        if endpoints.insert(name.to_string(), settings).is_some() {
+            return Err(invalid("network is attached more than once"));
+        }
+    }
+
+    Ok(Some(NetworkingConfig {
+        endpoints_config: Some(endpoints),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use agent_wire::deploything::v1::NetworkAttachment;
+    use bollard::secret::Network;
+
+    use crate::docker_api::{
+        errors::DockerApiError,
+        labels,
+        network::{check_managed, networking_config},
+    };
+
+    fn attachment(network: &str, aliases: &[&str]) -> NetworkAttachment {
+        NetworkAttachment {
+            network: Some(network.into()),
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn attaches_to_each_network_with_aliases() {
+        let config = networking_config(&[
+            attachment("backend", &["api", "api-v2"]),
+            attachment("monitoring", &[]),
+        ])
+        .unwrap()
+        .unwrap();
+
+        let endpoints = config.endpoints_config.unwrap();
+        assert_eq!(
+            Some(vec!["api".to_string(), "api-v2".to_string()]),
+            endpoints["backend"].aliases
+        );
+        assert_eq!(None, endpoints["monitoring"].aliases);
+    }
+
+    #[test]
+    fn rejects_unnamed_network() {
+        assert!(matches!(
+            networking_config(&[attachment("", &[])]),
+            Err(DockerApiError::InvalidNetwork { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_network_attached_twice() {
+        assert!(matches!(
+            networking_config(&[attachment("backend", &[]), attachment("backend", &["db"])]),
+            Err(DockerApiError::InvalidNetwork { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_network_modes() {
+        for name in ["host", "none", "container:3f2a9c"] {
+            assert!(
+                matches!(
+                    networking_config(&[attachment(name, &[])]),
+                    Err(DockerApiError::InvalidNetwork { .. })
+                ),
+                "{name:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn only_managed_networks_are_joined() {
+        let managed = Network {
+            labels: Some(labels::managed()),
+            ..Default::default()
+        };
+        assert!(check_managed("backend", &managed).is_ok());
+
+        assert!(matches!(
+            check_managed("bridge", &Network::default()),
+            Err(DockerApiError::NetworkNotManaged { .. })
+        ));
+    }
+}
@@ -0,0 +1,153 @@
+use std::{collections::HashMap, net::IpAddr};
+
+use agent_wire::deploything::v1::{PortMap, PublishedPort};
+use bollard::secret::PortBinding;
+
+use crate::docker_api::errors::DockerApiError;
+
+const PROTOCOLS: [&str; 3] = ["tcp", "udp", "sctp"];
+
+/// Validates `port_maps` and groups them into Docker port bindings, keyed by `port/protocol`.
+pub fn port_bindings(
+    port_maps: &[PortMap],
+) -> Result<HashMap<String, Option<Vec<PortBinding>>>, DockerApiError> {
+    let mut bindings: HashMap<String, Option<Vec<PortBinding>>> = HashMap::new();
+
+    for port_map in port_maps {
+        let (container_port, binding) = parse(port_map)?;
+        bindings
+            .entry(container_port)
+            .or_default()
+            .get_or_insert_default()
+            .push(binding);
+    }
+
+    Ok(bindings)
+}
+
+fn parse(port_map: &PortMap) -> Result<(String, PortBinding), DockerApiError> {
+    let invalid = |reason| DockerApiError::InvalidPortMap {
+        from: port_map.from().to_string(),
+        reason,
+    };
+
+    let (port, protocol) = match port_map.from().split_once('/') {
+        Some((port, protocol)) => (port, protocol.to_ascii_lowercase()),
+        None => (port_map.from(), "tcp".to_string()),
+    };
+
+    if port.is_empty() {
+        return Err(invalid("container port is missing"));
+    }
+    parse_port(port).ok_or_else(|| invalid("container port is not a valid port number"))?;
+
+    if !PROTOCOLS.contains(&protocol.as_str()) {
+        return Err(invalid("protocol must be one of tcp, udp or sctp"));
+    }
+
+    // An empty host port tells Docker to pick a random free port.
+    let host_port = match port_map.to() {
+        "" => None,
+        to => {
+            parse_port(to).ok_or_else(|| invalid("host port is not a valid port number"))?;
+            Some(to.to_string())
+        }
+    };
+
+    let host_ip = match port_map.host_ip() {
+        "" => None,
+        ip => {
+            ip.parse::<IpAddr>()
+                .map_err(|_| invalid("host IP is not a valid IP address"))?;
+            Some(ip.to_string())
+        }
+    };
+
+    let binding = PortBinding { host_ip, host_port };
+
+    Ok((format!("{port}/{protocol}"), binding))
+}
+
+fn parse_port(port: &str) -> Option<u16> {
+    port.parse().ok().filter(|port| *port != 0)
+}
+
+/// Flattens the port bindings Docker reports for a running container.
+pub fn published_ports(ports: &HashMap<String, Option<Vec<PortBinding>>>) -> Vec<PublishedPort> {
+    let mut published: Vec<_> = ports
+        .iter()
+        .flat_map(|(container_port, bindings)| {
+            bindings.iter().flatten().map(|binding| PublishedPort {
+                container_port: Some(container_port.clone()),
+                host_ip: binding.host_ip.clone(),
+                host_port: binding
+                    .host_port
+                    .as_deref()
+                    .and_then(parse_port)
+                    .map(u32::from),
+            })
+        })
+        .collect();
+
+    published.sort_by(|a, b| {
+        (a.container_port(), a.host_ip(), a.host_port()).cmp(&(
+            b.container_port(),
+            b.host_ip(),
+            b.host_port(),
+        ))
+    });
+
+    published
+}
+
+#[cfg(test)]
+mod test {
+    use agent_wire::deploything::v1::PortMap;
+
+    use crate::docker_api::{errors::DockerApiError, ports::port_bindings};
+
+    fn port_map(from: &str, to: Option<&str>, host_ip: Option<&str>) -> PortMap {
+        PortMap {
+            from: Some(from.into()),
+            to: to.map(Into::into),
+            host_ip: host_ip.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn groups_bindings_by_container_port() {
+        let bindings = port_bindings(&[
+            port_map("8080", Some("80"), None),
+            port_map("8080/tcp", Some("8080"), Some("127.0.0.1")),
+            port_map("53/UDP", None, None),
+        ])
+        .unwrap();
+
+        assert_eq!(2, bindings.len());
+        assert_eq!(2, bindings["8080/tcp"].as_ref().unwrap().len());
+
+        let dns = &bindings["53/udp"].as_ref().unwrap()[0];
+        assert_eq!(None, dns.host_port);
+        assert_eq!(None, dns.host_ip);
+    }
+
+    #[test]
+    fn rejects_invalid_port_maps() {
+        for pm in [
+            port_map("", Some("80"), None),
+            port_map("http", Some("80"), None),
+            port_map("0", Some("80"), None),
+            port_map("8080/icmp", Some("80"), None),
+            port_map("8080", Some("70000"), None),
+            port_map("8080", Some("80"), Some("localhost")),
+        ] {
+            assert!(
+                matches!(
+                    port_bindings(std::slice::from_ref(&pm)),
+                    Err(DockerApiError::InvalidPortMap { .. })
+                ),
+                "{pm:?} should be rejected"
+            );
+        }
+    }
+}
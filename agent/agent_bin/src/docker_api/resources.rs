@@ -0,0 +1,243 @@
+use agent_wire::deploything::v1::ResourceLimits;
+use bollard::secret::HostConfig;
+
+use crate::docker_api::errors::DockerApiError;
+
+/// Docker's default CFS scheduler period.
+const DEFAULT_CPU_PERIOD_US: i64 = 100_000;
+
+/// The smallest `--max-cpus` accepted.
+const MIN_MAX_CPUS: f64 = 0.01;
+
+/// The most resources the agent will grant a single container.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ResourceMaximums {
+    pub memory_bytes: Option<i64>,
+    pub cpu_shares: Option<i64>,
+    /// The number of CPUs, i.e. `cpu_quota_us / cpu_period_us`.
+    pub cpus: Option<f64>,
+    pub pids: Option<i64>,
+}
+
+impl ResourceMaximums {
+    /// Validates `limits` against the maximums and returns the limits to apply.
+    /// Limits that are unset are filled in with the corresponding maximum, so that no
+    /// container runs unbounded once a maximum has been configured.
+    pub fn enforce(
+        &self,
+        limits: Option<&ResourceLimits>,
+    ) -> Result<ResourceLimits, DockerApiError> {
+        let mut limits = limits.cloned().unwrap_or_default();
+
+        for (value, name) in [
+            (limits.memory_bytes, "memory"),
+            (limits.memory_reservation_bytes, "memory reservation"),
+            (limits.cpu_shares, "CPU shares"),
+            (limits.cpu_quota_us, "CPU quota"),
+            (limits.cpu_period_us, "CPU period"),
+            (limits.pids_limit, "pids limit"),
+        ] {
+            if value.is_some_and(|v| v <= 0) {
+                return Err(DockerApiError::InvalidResourceLimits {
+                    reason: format!("{name} must be positive"),
+                });
+            }
+        }
+
+        limits.memory_bytes = at_most("memory", limits.memory_bytes, self.memory_bytes)?;
+        limits.cpu_shares = at_most("CPU shares", limits.cpu_shares, self.cpu_shares)?;
+        limits.pids_limit = at_most("pids limit", limits.pids_limit, self.pids)?;
+
+        if let (Some(reservation), Some(memory)) =
+            (limits.memory_reservation_bytes, limits.memory_bytes)
+            && reservation > memory
+        {
+            return Err(DockerApiError::InvalidResourceLimits {
+                reason: "memory reservation exceeds the memory limit".to_string(),
+            });
+        }
+
+        if let Some(max_cpus) = self.cpus {
+            let period = limits.cpu_period_us.unwrap_or(DEFAULT_CPU_PERIOD_US);
+            let max_quota = (max_cpus * period as f64) as i64;
+
+            match limits.cpu_quota_us {
+                Some(quota) if quota > max_quota => {
+                    return Err(DockerApiError::ResourceLimitExceeded {
+                        resource: "CPUs",
+                        requested: format!("{:.2}", quota as f64 / period as f64),
+                        maximum: format!("{max_cpus:.2}"),
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    limits.cpu_quota_us = Some(max_quota);
+                    limits.cpu_period_us = Some(period);
+                }
+            }
+        }
+
+        Ok(limits)
+    }
+}
+
+fn at_most(
+    resource: &'static str,
+    requested: Option<i64>,
+    maximum: Option<i64>,
+) -> Result<Option<i64>, DockerApiError> {
+    match (requested, maximum) {
+        (Some(requested), Some(maximum)) if requested > maximum => {
+            Err(DockerApiError::ResourceLimitExceeded {
+                resource,
+                requested: requested.to_string(),
+                maximum: maximum.to_string(),
+            })
+        }
+        (None, maximum) => Ok(maximum),
+        (requested, _) => Ok(requested),
+    }
+}
+
+/// Parses a `--max-memory-bytes`, `--max-cpu-shares` or `--max-pids` maximum, so that one
+/// that no request could satisfy is reported at startup.
+pub fn parse_maximum(value: &str) -> Result<i64, String> {
+    match value.parse::<i64>() {
+        Ok(maximum) if maximum > 0 => Ok(maximum),
+        Ok(_) => Err("must be positive".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Parses `--max-cpus`. At least a hundredth of a CPU is required, since a smaller
+/// maximum rounds down to a CPU quota of zero, which Docker treats as unlimited.
+pub fn parse_max_cpus(value: &str) -> Result<f64, String> {
+    let cpus = value.parse::<f64>().map_err(|e| e.to_string())?;
+
+    if !cpus.is_finite() || cpus < MIN_MAX_CPUS {
+        return Err(format!("must be a number of at least {MIN_MAX_CPUS}"));
+    }
+
+    Ok(cpus)
+}
+
+/// Maps `limits` onto the corresponding `HostConfig` fields.
+pub fn apply(limits: &ResourceLimits, host_config: &mut HostConfig) {
+    host_config.memory = limits.memory_bytes;
+    host_config.memory_reservation = limits.memory_reservation_bytes;
+    host_config.cpu_shares = limits.cpu_shares;
+    host_config.cpu_quota = limits.cpu_quota_us;
+    host_config.cpu_period = limits.cpu_period_us;
+    host_config.pids_limit = limits.pids_limit;
+}
+
+#[cfg(test)]
+mod test {
+    use agent_wire::deploything::v1::ResourceLimits;
+
+    use crate::docker_api::{
+        errors::DockerApiError,
+        resources::{ResourceMaximums, parse_max_cpus, parse_maximum},
+    };
+
+    const MAXIMUMS: ResourceMaximums = ResourceMaximums {
+        memory_bytes: Some(512 * 1024 * 1024),
+        cpu_shares: None,
+        cpus: Some(1.5),
+        pids: Some(256),
+    };
+
+    #[test]
+    fn unset_limits_default_to_maximums() {
+        let limits = MAXIMUMS.enforce(None).unwrap();
+
+        assert_eq!(Some(512 * 1024 * 1024), limits.memory_bytes);
+        assert_eq!(None, limits.cpu_shares);
+        assert_eq!(Some(150_000), limits.cpu_quota_us);
+        assert_eq!(Some(100_000), limits.cpu_period_us);
+        assert_eq!(Some(256), limits.pids_limit);
+    }
+
+    #[test]
+    fn limits_within_maximums_are_kept() {
+        let requested = ResourceLimits {
+            memory_bytes: Some(128 * 1024 * 1024),
+            memory_reservation_bytes: Some(64 * 1024 * 1024),
+            cpu_shares: Some(2048),
+            cpu_quota_us: Some(50_000),
+            cpu_period_us: Some(50_000),
+            pids_limit: Some(64),
+        };
+
+        assert_eq!(requested, MAXIMUMS.enforce(Some(&requested)).unwrap());
+    }
+
+    #[test]
+    fn over_limit_requests_are_rejected() {
+        for requested in [
+            ResourceLimits {
+                memory_bytes: Some(1024 * 1024 * 1024),
+                ..Default::default()
+            },
+            ResourceLimits {
+                cpu_quota_us: Some(200_000),
+                ..Default::default()
+            },
+            ResourceLimits {
+                pids_limit: Some(1000),
+                ..Default::default()
+            },
+        ] {
+            assert!(
+                matches!(
+                    MAXIMUMS.enforce(Some(&requested)),
+                    Err(DockerApiError::ResourceLimitExceeded { .. })
+                ),
+                "{requested:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_limits_are_rejected() {
+        for requested in [
+            ResourceLimits {
+                memory_bytes: Some(-1),
+                ..Default::default()
+            },
+            ResourceLimits {
+                memory_bytes: Some(64 * 1024 * 1024),
+                memory_reservation_bytes: Some(128 * 1024 * 1024),
+                ..Default::default()
+            },
+        ] {
+            assert!(
+                matches!(
+                    ResourceMaximums::default().enforce(Some(&requested)),
+                    Err(DockerApiError::InvalidResourceLimits { .. })
+                ),
+                "{requested:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn maximums_must_be_positive() {
+        assert_eq!(parse_maximum("256"), Ok(256));
+        assert_eq!(parse_max_cpus("1.5"), Ok(1.5));
+
+        for value in ["0", "-1", "", "1.5"] {
+            assert!(
+                parse_maximum(value).is_err(),
+                "{value:?} should be rejected"
+            );
+        }
+
+        for value in ["0", "-1", "0.001", "NaN", "inf", ""] {
+            assert!(
+                parse_max_cpus(value).is_err(),
+                "{value:?} should be rejected"
+            );
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use agent_wire::deploything::v1::ContainerStats;
+use bollard::{
+    Docker,
+    query_parameters::{ListContainersOptionsBuilder, StatsOptionsBuilder},
+};
+use futures_util::future::join_all;
+use tokio_stream::StreamExt;
+use tracing::{error, instrument, warn};
+
+use crate::docker_api::labels;
+
+/// How long to wait for a single container's stats. Docker samples CPU usage twice
+/// before responding, so a healthy response takes around a second.
+const STATS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The latest resource usage of each running managed container, keyed by container ID.
+/// Stats are collected in the background by a `StatsCollector` so that slow stats calls
+/// never hold up a snapshot.
+#[derive(Debug, Default, Clone)]
+pub struct StatsCache {
+    stats: Arc<RwLock<HashMap<String, ContainerStats>>>,
+}
+
+impl StatsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, container_id: &str) -> Option<ContainerStats> {
+        self.stats.read().unwrap().get(container_id).cloned()
+    }
+
+    fn replace(&self, stats: HashMap<String, ContainerStats>) {
+        *self.stats.write().unwrap() = stats;
+    }
+}
+
+pub struct StatsCollector<'a> {
+    docker: &'a Docker,
+    cache: StatsCache,
+    interval: Duration,
+}
+
+impl<'a> StatsCollector<'a> {
+    pub fn new(docker: &'a Docker, cache: StatsCache, interval: Duration) -> Self {
+        Self {
+            docker,
+            cache,
+            interval,
+        }
+    }
+
+    pub async fn run(&mut self) {
+        loop {
+            self.collect().await;
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn collect(&self) {
+        let mut filters = labels::managed_filter();
+        filters.insert("status", vec!["running".to_string()]);
+        let options = ListContainersOptionsBuilder::new()
+            .filters(&filters)
+            .build();
+
+        let containers = match self.docker.list_containers(Some(options)).await {
+            Ok(containers) => containers,
+            Err(e) => {
+                error!("List containers failed: {e}");
+                return;
+            }
+        };
+
+        let ids = containers.into_iter().filter_map(|c| c.id);
+        let stats = join_all(ids.map(|id| async move {
+            let stats = stats(self.docker, &id).await;
+            stats.map(|stats| (id, stats))
+        }))
+        .await;
+
+        self.cache.replace(stats.into_iter().flatten().collect());
+    }
+}
+
+async fn stats(docker: &Docker, container_id: &str) -> Option<ContainerStats> {
+    let options = StatsOptionsBuilder::new()
+        .stream(false)
+        .one_shot(false)
+        .build();
+    let mut stream = docker.stats(container_id, Some(options));
+
+    match tokio::time::timeout(STATS_TIMEOUT, stream.next()).await {
+        Ok(Some(Ok(response))) => Some(ContainerStats::from(&response)),
+        Ok(Some(Err(e))) => {
+            warn!("Container stats failed for {container_id}: {e}");
+            None
+        }
+        Ok(None) => None,
+        Err(_) => {
+            warn!("Container stats timed out for {container_id}");
+            None
+        }
+    }
+}
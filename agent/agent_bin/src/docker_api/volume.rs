@@ -0,0 +1,120 @@
+use agent_wire::deploything::v1::Volume;
+use bollard::{
+    Docker,
+    query_parameters::{ListVolumesOptionsBuilder, RemoveVolumeOptionsBuilder},
+    secret::{Volume as DockerVolume, VolumeCreateOptions},
+};
+use tracing::{error, info, instrument};
+
+use crate::docker_api::{errors::DockerApiError, labels};
+
+/// Makes sure the named volume exists, creating it as a managed volume if it does not.
+/// An existing volume must be managed, so containers cannot mount another workload's data,
+/// or a volume whose driver binds a host directory outside the bind allowlist.
+#[instrument(skip(docker))]
+pub async fn ensure(docker: &Docker, name: &str) -> Result<(), DockerApiError> {
+    if let Ok(volume) = docker.inspect_volume(name).await {
+        return check_managed(name, &volume);
+    }
+
+    info!("Creating volume");
+
+    let options = VolumeCreateOptions {
+        name: Some(name.to_string()),
+        labels: Some(labels::managed()),
+        ..Default::default()
+    };
+
+    match docker.create_volume(options).await {
+        Ok(_) => {
+            info!("Volume create complete");
+            Ok(())
+        }
+        Err(e) => {
+            error!("Volume create failed: {e}");
+            Err(DockerApiError::VolumeCreateFailed {
+                name: name.to_string(),
+            })
+        }
+    }
+}
+
+#[instrument(skip(docker))]
+pub async fn list(docker: &Docker) -> Result<Vec<Volume>, DockerApiError> {
+    let options = ListVolumesOptionsBuilder::new()
+        .filters(&labels::managed_filter())
+        .build();
+
+    match docker.list_volumes(Some(options)).await {
+        Ok(res) => {
+            info!("List volumes complete");
+            let volumes = res.volumes.unwrap_or_default();
+            Ok(volumes.iter().map(|v| v.into()).collect())
+        }
+        Err(e) => {
+            error!("List volumes failed: {e}");
+            Err(DockerApiError::ListVolumesFailed)
+        }
+    }
+}
+
+/// Removes a managed volume. Volumes the agent did not create are never removed.
+#[instrument(skip(docker))]
+pub async fn remove(docker: &Docker, name: &str, force: bool) -> Result<(), DockerApiError> {
+    let volume = docker.inspect_volume(name).await.map_err(|e| {
+        error!("Volume inspect failed: {e}");
+        DockerApiError::VolumeRemoveFailed {
+            name: name.to_string(),
+        }
+    })?;
+
+    check_managed(name, &volume)?;
+
+    info!("Removing volume");
+
+    let options = RemoveVolumeOptionsBuilder::new().force(force).build();
+
+    match docker.remove_volume(name, Some(options)).await {
+        Ok(_) => {
+            info!("Volume removed");
+            Ok(())
+        }
+        Err(e) => {
+            error!("Volume remove failed: {e}");
+            Err(DockerApiError::VolumeRemoveFailed {
+                name: name.to_string(),
+            })
+        }
+    }
+}
+
+fn check_managed(name: &str, volume: &DockerVolume) -> Result<(), DockerApiError> {
+    if !labels::is_managed(&volume.labels) {
+        return Err(DockerApiError::VolumeNotManaged {
+            name: name.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use bollard::secret::Volume as DockerVolume;
+
+    use crate::docker_api::{errors::DockerApiError, labels, volume::check_managed};
+
+    #[test]
+    fn only_managed_volumes_are_used() {
+        let managed = DockerVolume {
+            labels: labels::managed(),
+            ..Default::default()
+        };
+        assert!(check_managed("pgdata", &managed).is_ok());
+
+        assert!(matches!(
+            check_managed("pgdata", &DockerVolume::default()),
+            Err(DockerApiError::VolumeNotManaged { .. })
+        ));
+    }
+}
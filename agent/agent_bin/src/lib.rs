@@ -1,4 +1,6 @@
 pub mod cli;
 pub mod cmd;
 pub mod docker_api;
+pub mod reconcile;
+pub mod snapshot;
 pub mod ws;
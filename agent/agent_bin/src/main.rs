@@ -2,19 +2,28 @@ use std::{sync::Arc, time::Duration};
 
 use agent_bin::{
     cli::AgentCli,
-    cmd::CommandHandler,
-    docker_api::{self, DockerEventsHandler},
+    cmd::{CommandHandler, CommandPolicy},
+    docker_api::{
+        BindAllowlist, DockerEventsHandler, ExecAllowlist, ResourceMaximums, StatsCache,
+        StatsCollector, StopOptions,
+    },
+    reconcile::Reconciler,
+    snapshot::{self, SnapshotUpdater},
     ws::{receiver::WsReceiver, sender::WsSender},
 };
 use agent_proxy::server::ReverseProxy;
 use bollard::Docker;
 use clap::Parser;
 use futures_util::{StreamExt, future::join_all};
-use prost::Message as _;
 use tokio::net::TcpListener;
-use tokio_tungstenite::tungstenite::Message;
 use tracing::instrument;
 
+/// How often resource usage is collected for snapshots.
+const STATS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often containers are reconciled towards the desired state when it has not changed.
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(10);
+
 #[tokio::main]
 async fn main() {
     let subscriber = tracing_subscriber::FmtSubscriber::new();
@@ -28,12 +37,38 @@ async fn main() {
             control_plane_port,
             snapshot_interval_secs,
             proxy_port,
+            max_memory_bytes,
+            max_cpu_shares,
+            max_cpus,
+            max_pids,
+            include_unmanaged,
+            exec_allow,
+            bind_allow,
+            stop_timeout_secs,
+            stop_signal,
         } => {
+            let policy = CommandPolicy {
+                maximums: ResourceMaximums {
+                    memory_bytes: max_memory_bytes,
+                    cpu_shares: max_cpu_shares,
+                    cpus: max_cpus,
+                    pids: max_pids,
+                },
+                exec_allowlist: ExecAllowlist::new(exec_allow),
+                bind_allowlist: BindAllowlist::new(bind_allow),
+                stop: StopOptions {
+                    timeout: Duration::from_secs(stop_timeout_secs as u64),
+                    signal: stop_signal,
+                },
+            };
+
             run(
                 &control_plane_hostname,
                 control_plane_port,
                 snapshot_interval_secs,
                 proxy_port,
+                policy,
+                include_unmanaged,
             )
             .await
         }
@@ -41,33 +76,74 @@ async fn main() {
 }
 
 #[instrument]
-async fn run(hostname: &str, port: u16, snapshot_interval_secs: u16, proxy_port: u16) {
+async fn run(
+    hostname: &str,
+    port: u16,
+    snapshot_interval_secs: u16,
+    proxy_port: u16,
+    policy: CommandPolicy,
+    include_unmanaged: bool,
+) {
     let uri = format!("ws://{hostname}:{port}");
 
     let docker = Docker::connect_with_defaults().unwrap();
     let docker = Arc::new(docker);
 
     let (cmd_tx, cmd_rx) = tokio::sync::mpsc::channel(16);
+    let (msg_tx, msg_rx) = tokio::sync::mpsc::channel(16);
+    let (bulk_tx, bulk_rx) = tokio::sync::mpsc::channel(64);
+    let (snapshot_trigger, snapshot_requests) = snapshot::trigger();
+    let (desired_tx, desired_rx) = tokio::sync::watch::channel(None);
+
+    let proxy = ReverseProxy::new();
+    let routes = proxy.route_table();
 
     let cmd_handler = {
         let docker = docker.clone();
+        let msg_tx = msg_tx.clone();
+        let snapshot_trigger = snapshot_trigger.clone();
+        let policy = policy.clone();
         tokio::task::spawn(async move {
-            let mut cmd_handler = CommandHandler::new(&docker, cmd_rx);
+            let mut cmd_handler = CommandHandler::new(
+                &docker,
+                cmd_rx,
+                msg_tx,
+                bulk_tx,
+                policy,
+                snapshot_trigger,
+                desired_tx,
+            );
             cmd_handler.handle_incoming().await;
         })
     };
 
+    let reconciler = {
+        let docker = docker.clone();
+        let msg_tx = msg_tx.clone();
+        tokio::task::spawn(async move {
+            let mut reconciler = Reconciler::new(
+                &docker,
+                desired_rx,
+                msg_tx,
+                routes,
+                policy,
+                RECONCILE_INTERVAL,
+            );
+            reconciler.run().await;
+        })
+    };
+
     let events_monitor = {
         let docker = docker.clone();
+        let msg_tx = msg_tx.clone();
         tokio::task::spawn(async move {
-            let mut events_handler = DockerEventsHandler::new(&docker);
-            let _ = events_handler.listen().await;
+            let mut events_handler = DockerEventsHandler::new(&docker, msg_tx, snapshot_trigger);
+            events_handler.listen().await;
         })
     };
 
     let (stream, _) = tokio_tungstenite::connect_async(&uri).await.unwrap();
     let (sink, stream) = stream.split();
-    let (msg_tx, msg_rx) = tokio::sync::mpsc::channel(16);
 
     let ws_receiver = {
         let msg_tx = msg_tx.clone();
@@ -78,23 +154,35 @@ async fn run(hostname: &str, port: u16, snapshot_interval_secs: u16, proxy_port:
     };
 
     let ws_sender = tokio::task::spawn(async move {
-        let mut sender = WsSender::new(sink, msg_rx);
+        let mut sender = WsSender::new(sink, msg_rx, bulk_rx);
         sender.handle().await.unwrap();
     });
 
+    let stats = StatsCache::new();
+
+    let stats_collector = {
+        let docker = docker.clone();
+        let stats = stats.clone();
+        tokio::task::spawn(async move {
+            let mut collector = StatsCollector::new(&docker, stats, STATS_INTERVAL);
+            collector.run().await;
+        })
+    };
+
     let snapshot_updater = tokio::task::spawn(async move {
-        loop {
-            tokio::time::sleep(Duration::from_secs(snapshot_interval_secs as u64)).await;
-            let snapshot = docker_api::build_snapshot(&docker)
-                .await
-                .unwrap()
-                .encode_to_vec();
-            msg_tx.send(Message::Binary(snapshot.into())).await.unwrap();
-        }
+        let interval = Duration::from_secs(snapshot_interval_secs as u64);
+        let mut updater = SnapshotUpdater::new(
+            &docker,
+            msg_tx,
+            snapshot_requests,
+            stats,
+            interval,
+            include_unmanaged,
+        );
+        updater.run().await;
     });
 
     let proxy_serve = tokio::task::spawn(async move {
-        let proxy = ReverseProxy::new();
         let addr = format!("localhost:{proxy_port}");
         let listener = TcpListener::bind(addr).await.unwrap();
         proxy.serve(listener).await;
@@ -102,7 +190,9 @@ async fn run(hostname: &str, port: u16, snapshot_interval_secs: u16, proxy_port:
 
     let tasks = vec![
         cmd_handler,
+        reconciler,
         events_monitor,
+        stats_collector,
         ws_receiver,
         ws_sender,
         snapshot_updater,
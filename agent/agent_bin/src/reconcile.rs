@@ -0,0 +1,804 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    net::IpAddr,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use agent_proxy::route::{RouteMatchBuilder, RouteTable, Service};
+use agent_wire::deploything::v1::{
+    AppRoute, AppStatus, ContainerHealth, ContainerState, ContainerStatus, DesiredApp,
+    DesiredState, ReconcileStatus, RunParams,
+};
+use bollard::Docker;
+use prost::Message as _;
+use ring::digest;
+use tokio::sync::{mpsc::Sender, watch};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, info, instrument, warn};
+
+use crate::{
+    cmd::CommandPolicy,
+    docker_api::{self, Container, InspectCache, Replica},
+    ws::encode_message,
+};
+
+/// The most replicas of a single app the agent will run.
+pub const MAX_REPLICAS: u32 = 64;
+
+/// Checks a desired state before it is accepted, so that mistakes are reported to the
+/// control plane straight away rather than as reconciliation errors.
+pub fn validate(desired: &DesiredState) -> Result<(), String> {
+    let mut app_names = HashSet::new();
+
+    for app in &desired.apps {
+        let app_name = app.app_name();
+
+        docker_api::validate_app_name(app_name).map_err(|e| e.to_string())?;
+
+        if !app_names.insert(app_name) {
+            return Err(format!("app {app_name} is listed more than once"));
+        }
+
+        if app.run.is_none() {
+            return Err(format!("app {app_name} has no run parameters"));
+        }
+
+        if app.replicas() > MAX_REPLICAS {
+            return Err(format!(
+                "app {app_name} exceeds the maximum of {MAX_REPLICAS} replicas"
+            ));
+        }
+
+        if app.replicas() > 1 && app.run.as_ref().is_some_and(has_fixed_host_ports) {
+            return Err(format!(
+                "app {app_name} publishes fixed host ports, so it cannot run more than one replica"
+            ));
+        }
+
+        for route in &app.routes {
+            if route.hostname().is_empty() && route.path().is_empty() {
+                return Err(format!("a route of app {app_name} has no hostname or path"));
+            }
+
+            if u16::try_from(route.port()).is_err() || route.port() == 0 {
+                return Err(format!("a route of app {app_name} has an invalid port"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `run` publishes a port on a chosen host port, which only one container can hold
+/// at a time.
+fn has_fixed_host_ports(run: &RunParams) -> bool {
+    run.container_host_config
+        .as_ref()
+        .is_some_and(|config| config.port_maps.iter().any(|map| !map.to().is_empty()))
+}
+
+/// How many bytes of the SHA-256 digest are kept, so that the revision stays short enough
+/// for container names while a colliding configuration remains out of reach.
+const REVISION_BYTES: usize = 16;
+
+/// Identifies an app's configuration, so that replicas created from an earlier
+/// configuration can be recognised. Maps are hashed in key order, since their iteration
+/// order is not stable, and the names the agent assigns are ignored.
+pub fn revision(run: &RunParams) -> String {
+    let mut run = run.clone();
+    let env: BTreeMap<_, _> = std::mem::take(&mut run.env).into_iter().collect();
+    let secrets: BTreeMap<_, _> = std::mem::take(&mut run.secrets)
+        .into_iter()
+        .map(|(name, secret)| (name, secret.value().to_string()))
+        .collect();
+    let labels: BTreeMap<_, _> = std::mem::take(&mut run.labels).into_iter().collect();
+    run.app_name = None;
+    run.deployment_id = None;
+
+    let mut hasher = digest::Context::new(&digest::SHA256);
+    hasher.update(&run.encode_to_vec());
+
+    for map in [env, secrets, labels] {
+        // Separates the maps, so that an entry cannot be moved from one to the next.
+        hasher.update(&[0xff]);
+        for (key, value) in map {
+            hasher.update(key.as_bytes());
+            hasher.update(&[0]);
+            hasher.update(value.as_bytes());
+            hasher.update(&[0]);
+        }
+    }
+
+    hasher.finish().as_ref()[..REVISION_BYTES]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// What an app should look like once reconciled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Target {
+    revision: String,
+    replicas: u32,
+    /// Replicas hold fixed host ports, so a replacement cannot start alongside the replica
+    /// it replaces.
+    fixed_host_ports: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    Create {
+        app_name: String,
+        index: u32,
+    },
+    Remove {
+        app_name: String,
+        container_id: String,
+        running: bool,
+    },
+}
+
+/// Works out the actions that bring `containers` in line with `targets`, in the order
+/// they should be taken. Failed replicas are removed before they are recreated, since the
+/// replacement reuses their name. Replicas at an old revision are only removed once their
+/// replacements have been created, unless they hold fixed host ports their replacements
+/// need.
+fn plan(targets: &BTreeMap<String, Target>, containers: &[ContainerStatus]) -> Vec<Action> {
+    let mut current = HashSet::new();
+    let mut removed_first = Vec::new();
+    let mut unwanted = Vec::new();
+
+    for status in containers {
+        let (Some(replica), Some(container_id)) =
+            (Replica::from_labels(&status.labels), status.id.clone())
+        else {
+            continue;
+        };
+
+        let remove = Action::Remove {
+            app_name: replica.app_name.clone(),
+            container_id,
+            running: status.container_state() == ContainerState::Running,
+        };
+
+        match targets.get(&replica.app_name) {
+            Some(target) if is_current(&replica, target) => {
+                if has_failed(status) {
+                    removed_first.push(remove);
+                } else {
+                    current.insert((replica.app_name, replica.index));
+                }
+            }
+            Some(target) if target.fixed_host_ports => removed_first.push(remove),
+            _ => unwanted.push(remove),
+        }
+    }
+
+    let mut actions = removed_first;
+
+    for (app_name, target) in targets {
+        for index in 0..target.replicas {
+            if !current.contains(&(app_name.clone(), index)) {
+                actions.push(Action::Create {
+                    app_name: app_name.clone(),
+                    index,
+                });
+            }
+        }
+    }
+
+    actions.extend(unwanted);
+    actions
+}
+
+fn is_current(replica: &Replica, target: &Target) -> bool {
+    replica.revision == target.revision && replica.index < target.replicas
+}
+
+/// Replicas that are not running and will not be restarted by Docker.
+fn has_failed(status: &ContainerStatus) -> bool {
+    matches!(
+        status.container_state(),
+        ContainerState::Created | ContainerState::Exited | ContainerState::Dead
+    )
+}
+
+fn is_ready(status: &ContainerStatus) -> bool {
+    status.container_state() == ContainerState::Running
+        && !matches!(
+            status.health(),
+            ContainerHealth::Starting | ContainerHealth::Unhealthy
+        )
+}
+
+/// The ready replicas of `app_name` at the target revision, in replica order.
+fn ready_replicas<'c>(
+    app_name: &str,
+    target: &Target,
+    containers: &'c [ContainerStatus],
+) -> Vec<(u32, &'c ContainerStatus)> {
+    let mut ready: Vec<_> = containers
+        .iter()
+        .filter_map(|status| {
+            let replica = Replica::from_labels(&status.labels)?;
+            (replica.app_name == app_name && is_current(&replica, target) && is_ready(status))
+                .then_some((replica.index, status))
+        })
+        .collect();
+
+    ready.sort_by_key(|(index, _)| *index);
+    ready
+}
+
+fn app_status(
+    app_name: &str,
+    target: &Target,
+    containers: &[ContainerStatus],
+    error: Option<String>,
+) -> AppStatus {
+    let ready = ready_replicas(app_name, target, containers).len() as u32;
+
+    let extra = containers.iter().any(|status| {
+        Replica::from_labels(&status.labels)
+            .is_some_and(|replica| replica.app_name == app_name && !is_current(&replica, target))
+    });
+
+    AppStatus {
+        app_name: Some(app_name.to_string()),
+        revision: Some(target.revision.clone()),
+        desired_replicas: Some(target.replicas),
+        ready_replicas: Some(ready),
+        converged: Some(ready == target.replicas && !extra && error.is_none()),
+        error,
+    }
+}
+
+/// Where the proxy should send requests for `route` to reach a replica.
+fn route_service(
+    app: &DesiredApp,
+    index: u32,
+    status: &ContainerStatus,
+    route: &AppRoute,
+) -> Option<Service> {
+    let name = format!("{}-{index}", app.app_name());
+    let port = u16::try_from(route.port()).ok()?;
+
+    let network = app.run.as_ref().and_then(|run| run.networks.first());
+    if let Some(network) = network {
+        let address = status
+            .networks
+            .iter()
+            .find(|n| n.name() == network.network())?
+            .ip_address()
+            .parse::<IpAddr>()
+            .ok()?;
+        return Some(Service::at(&name, address, port));
+    }
+
+    let container_port = format!("{port}/tcp");
+    let host_port = status
+        .ports
+        .iter()
+        .find(|p| p.container_port() == container_port)?
+        .host_port();
+
+    Some(Service::new(&name, u16::try_from(host_port).ok()?))
+}
+
+/// Reconciles the agent's containers towards the most recently received `DesiredState`,
+/// whenever it changes and again after `interval`, so that replicas that crash or are
+/// tampered with are replaced. Nothing is done until a desired state has been received.
+///
+/// The desired state is not persisted, so after a restart the agent waits for the control
+/// plane to send it again.
+pub struct Reconciler<'a> {
+    docker: &'a Docker,
+    desired_rx: watch::Receiver<Option<DesiredState>>,
+    msg_tx: Sender<Message>,
+    routes: Arc<RwLock<RouteTable>>,
+    policy: CommandPolicy,
+    inspected: InspectCache,
+    interval: Duration,
+    /// The last status sent, without its timestamp.
+    sent: Option<ReconcileStatus>,
+}
+
+impl<'a> Reconciler<'a> {
+    pub fn new(
+        docker: &'a Docker,
+        desired_rx: watch::Receiver<Option<DesiredState>>,
+        msg_tx: Sender<Message>,
+        routes: Arc<RwLock<RouteTable>>,
+        policy: CommandPolicy,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            docker,
+            desired_rx,
+            msg_tx,
+            routes,
+            policy,
+            inspected: InspectCache::new(),
+            interval,
+            sent: None,
+        }
+    }
+
+    pub async fn run(&mut self) {
+        loop {
+            let desired = self.desired_rx.borrow_and_update().clone();
+
+            if let Some(desired) = desired
+                && !self.reconcile(&desired).await
+            {
+                error!("Message channel closed, no longer reconciling");
+                return;
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(self.interval) => {}
+                changed = self.desired_rx.changed() => {
+                    if changed.is_err() {
+                        error!("Desired state channel closed, no longer reconciling");
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns `false` if the message channel has closed.
+    #[instrument(skip_all, fields(generation = desired.generation()))]
+    async fn reconcile(&mut self, desired: &DesiredState) -> bool {
+        let apps: BTreeMap<String, &DesiredApp> = desired
+            .apps
+            .iter()
+            .map(|app| (app.app_name().to_string(), app))
+            .collect();
+
+        let targets: BTreeMap<String, Target> = apps
+            .iter()
+            .map(|(app_name, app)| {
+                let run = app.run.clone().unwrap_or_default();
+                let target = Target {
+                    revision: revision(&run),
+                    replicas: app.replicas(),
+                    fixed_host_ports: has_fixed_host_ports(&run),
+                };
+                (app_name.clone(), target)
+            })
+            .collect();
+
+        let containers = match docker_api::list_containers(self.docker, &mut self.inspected).await {
+            Ok(containers) => containers,
+            Err(e) => {
+                error!("Failed to list containers: {e}");
+                return true;
+            }
+        };
+
+        let actions = plan(&targets, &containers);
+        let mut errors = HashMap::new();
+
+        for action in &actions {
+            if let Err((app_name, e)) = self.apply(action, &apps, &targets).await {
+                warn!("Reconciliation of {app_name} failed: {e}");
+                errors.insert(app_name, e);
+            }
+        }
+
+        let containers = if actions.is_empty() {
+            containers
+        } else {
+            match docker_api::list_containers(self.docker, &mut self.inspected).await {
+                Ok(containers) => containers,
+                Err(e) => {
+                    error!("Failed to list containers: {e}");
+                    return true;
+                }
+            }
+        };
+
+        self.update_routes(&apps, &targets, &containers);
+
+        let status = ReconcileStatus {
+            generation: desired.generation,
+            timestamp: None,
+            apps: targets
+                .iter()
+                .map(|(app_name, target)| {
+                    app_status(app_name, target, &containers, errors.remove(app_name))
+                })
+                .collect(),
+        };
+
+        if self.sent.as_ref() == Some(&status) {
+            return true;
+        }
+
+        let message = encode_message(ReconcileStatus {
+            timestamp: Some(SystemTime::now().into()),
+            ..status.clone()
+        });
+        self.sent = Some(status);
+
+        self.msg_tx.send(message).await.is_ok()
+    }
+
+    /// On failure, returns the app the action was for along with the reason.
+    #[instrument(skip(self, apps, targets))]
+    async fn apply(
+        &self,
+        action: &Action,
+        apps: &BTreeMap<String, &DesiredApp>,
+        targets: &BTreeMap<String, Target>,
+    ) -> Result<(), (String, String)> {
+        match action {
+            Action::Create { app_name, index } => {
+                let (Some(app), Some(target)) = (apps.get(app_name), targets.get(app_name)) else {
+                    return Ok(());
+                };
+
+                let replica = Replica {
+                    app_name: app_name.clone(),
+                    revision: target.revision.clone(),
+                    index: *index,
+                };
+
+                self.create(app, &replica)
+                    .await
+                    .map_err(|e| (app_name.clone(), format!("Failed to create replica: {e}")))
+            }
+            Action::Remove {
+                app_name,
+                container_id,
+                running,
+            } => {
+                // Give the container a chance to shut down gracefully before it is removed.
+                if *running
+                    && let Err(e) =
+                        docker_api::stop_container(self.docker, container_id, &self.policy.stop)
+                            .await
+                {
+                    warn!("Failed to stop {container_id}, removing it anyway: {e}");
+                }
+
+                docker_api::remove_container(self.docker, container_id, false, true)
+                    .await
+                    .map_err(|e| (app_name.clone(), format!("Failed to remove replica: {e}")))
+            }
+        }
+    }
+
+    async fn create(
+        &self,
+        app: &DesiredApp,
+        replica: &Replica,
+    ) -> Result<(), docker_api::DockerApiError> {
+        let mut params = app.run.clone().unwrap_or_default();
+        params.app_name = Some(replica.app_name.clone());
+        params.deployment_id = Some(format!("{}-{}", replica.revision, replica.index));
+
+        self.policy.apply(&mut params)?;
+
+        let container = Container::spawn_replica(self.docker, &params, replica).await?;
+        info!("Created replica {}", container.id());
+        Ok(())
+    }
+
+    /// Routes each app's routes to its ready replicas. The proxy sends requests to the first
+    /// matching entry, i.e. the lowest-numbered ready replica.
+    fn update_routes(
+        &self,
+        apps: &BTreeMap<String, &DesiredApp>,
+        targets: &BTreeMap<String, Target>,
+        containers: &[ContainerStatus],
+    ) {
+        let mut table = RouteTable::new();
+
+        for (app_name, app) in apps {
+            let Some(target) = targets.get(app_name) else {
+                continue;
+            };
+
+            for (index, status) in ready_replicas(app_name, target, containers) {
+                for route in &app.routes {
+                    let Some(service) = route_service(app, index, status, route) else {
+                        continue;
+                    };
+
+                    let mut route_match = RouteMatchBuilder::new();
+                    if let Some(hostname) = route.hostname.as_deref().filter(|h| !h.is_empty()) {
+                        route_match = route_match.hostname(hostname);
+                    }
+                    if let Some(path) = route.path.as_deref().filter(|p| !p.is_empty()) {
+                        route_match = route_match.path(path);
+                    }
+
+                    table.add(route_match.build(), service);
+                }
+            }
+        }
+
+        *self.routes.write().unwrap() = table;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, HashMap};
+
+    use agent_wire::deploything::v1::{
+        AppRoute, ContainerHealth, ContainerHostConfig, ContainerState, ContainerStatus,
+        DesiredApp, DesiredState, Mount, PortMap, RunParams, mount,
+    };
+
+    use crate::reconcile::{self, Action, Target};
+
+    fn run_params() -> RunParams {
+        RunParams {
+            image_name: Some("nginx".into()),
+            tag: Some("1.27".into()),
+            env: HashMap::from([
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "2".to_string()),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    fn replica(id: &str, app_name: &str, revision: &str, index: u32) -> ContainerStatus {
+        ContainerStatus {
+            id: Some(id.to_string()),
+            container_state: Some(ContainerState::Running as i32),
+            labels: HashMap::from([
+                ("deployth.ing/managed".to_string(), "true".to_string()),
+                ("deployth.ing/app".to_string(), app_name.to_string()),
+                ("deployth.ing/revision".to_string(), revision.to_string()),
+                ("deployth.ing/replica".to_string(), index.to_string()),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    fn targets(apps: &[(&str, &str, u32)]) -> BTreeMap<String, Target> {
+        apps.iter()
+            .map(|(app_name, revision, replicas)| {
+                let target = Target {
+                    revision: revision.to_string(),
+                    replicas: *replicas,
+                    fixed_host_ports: false,
+                };
+                (app_name.to_string(), target)
+            })
+            .collect()
+    }
+
+    fn create(app_name: &str, index: u32) -> Action {
+        Action::Create {
+            app_name: app_name.to_string(),
+            index,
+        }
+    }
+
+    fn remove(app_name: &str, container_id: &str, running: bool) -> Action {
+        Action::Remove {
+            app_name: app_name.to_string(),
+            container_id: container_id.to_string(),
+            running,
+        }
+    }
+
+    #[test]
+    fn revision_ignores_map_order_and_assigned_names() {
+        let a = run_params();
+        let mut b = run_params();
+        b.env = HashMap::from([
+            ("B".to_string(), "2".to_string()),
+            ("A".to_string(), "1".to_string()),
+        ]);
+        b.app_name = Some("web".into());
+        b.deployment_id = Some("abc-0".into());
+
+        assert_eq!(reconcile::revision(&a), reconcile::revision(&b));
+
+        b.tag = Some("1.28".into());
+        assert_ne!(reconcile::revision(&a), reconcile::revision(&b));
+    }
+
+    #[test]
+    fn revision_changes_with_nested_messages() {
+        let a = run_params();
+
+        let mut b = run_params();
+        b.container_host_config
+            .get_or_insert_default()
+            .resources
+            .get_or_insert_default()
+            .memory_bytes = Some(256 * 1024 * 1024);
+        assert_ne!(reconcile::revision(&a), reconcile::revision(&b));
+
+        let mut c = run_params();
+        c.container_host_config
+            .get_or_insert_default()
+            .mounts
+            .push(Mount {
+                source: Some(mount::Source::Volume("data".into())),
+                target: Some("/data".into()),
+                read_only: None,
+            });
+        assert_ne!(reconcile::revision(&a), reconcile::revision(&c));
+        assert_ne!(reconcile::revision(&b), reconcile::revision(&c));
+    }
+
+    #[test]
+    fn revision_is_a_truncated_sha256() {
+        let revision = reconcile::revision(&run_params());
+
+        assert_eq!(revision.len(), 32);
+        assert!(revision.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn revision_distinguishes_maps() {
+        let mut env = run_params();
+        env.env = HashMap::from([("KEY".to_string(), "value".to_string())]);
+        env.labels = HashMap::new();
+
+        let mut labels = run_params();
+        labels.env = HashMap::new();
+        labels.labels = HashMap::from([("KEY".to_string(), "value".to_string())]);
+
+        assert_ne!(reconcile::revision(&env), reconcile::revision(&labels));
+    }
+
+    #[test]
+    fn plan_creates_missing_replicas() {
+        let containers = [replica("a", "web", "r1", 0)];
+
+        assert_eq!(
+            reconcile::plan(&targets(&[("web", "r1", 3)]), &containers),
+            vec![create("web", 1), create("web", 2)]
+        );
+    }
+
+    #[test]
+    fn plan_replaces_drifted_replicas_after_creating_their_replacements() {
+        let containers = [replica("a", "web", "old", 0), replica("b", "web", "r1", 1)];
+
+        assert_eq!(
+            reconcile::plan(&targets(&[("web", "r1", 2)]), &containers),
+            vec![create("web", 0), remove("web", "a", true)]
+        );
+    }
+
+    #[test]
+    fn plan_replaces_replicas_with_fixed_host_ports_after_removing_them() {
+        let mut targets = targets(&[("web", "r1", 1)]);
+        targets.get_mut("web").unwrap().fixed_host_ports = true;
+
+        assert_eq!(
+            reconcile::plan(&targets, &[replica("a", "web", "old", 0)]),
+            vec![remove("web", "a", true), create("web", 0)]
+        );
+    }
+
+    #[test]
+    fn plan_removes_failed_replicas_before_recreating_them() {
+        let mut exited = replica("a", "web", "r1", 0);
+        exited.container_state = Some(ContainerState::Exited as i32);
+
+        assert_eq!(
+            reconcile::plan(&targets(&[("web", "r1", 1)]), &[exited]),
+            vec![remove("web", "a", false), create("web", 0)]
+        );
+    }
+
+    #[test]
+    fn plan_removes_extras_and_undesired_apps_but_not_other_containers() {
+        let unreconciled = ContainerStatus {
+            id: Some("d".to_string()),
+            labels: HashMap::from([("deployth.ing/app".to_string(), "api".to_string())]),
+            ..Default::default()
+        };
+        let containers = [
+            replica("a", "web", "r1", 0),
+            replica("b", "web", "r1", 1),
+            replica("c", "worker", "r1", 0),
+            unreconciled,
+        ];
+
+        assert_eq!(
+            reconcile::plan(&targets(&[("web", "r1", 1)]), &containers),
+            vec![remove("web", "b", true), remove("worker", "c", true)]
+        );
+    }
+
+    #[test]
+    fn app_status_converges_once_ready_without_extras() {
+        let target = Target {
+            revision: "r1".to_string(),
+            replicas: 2,
+            fixed_host_ports: false,
+        };
+        let mut starting = replica("b", "web", "r1", 1);
+        starting.health = Some(ContainerHealth::Starting as i32);
+
+        let mut containers = vec![replica("a", "web", "r1", 0), starting];
+        let status = reconcile::app_status("web", &target, &containers, None);
+        assert_eq!(status.ready_replicas, Some(1));
+        assert_eq!(status.converged, Some(false));
+
+        containers[1].health = Some(ContainerHealth::Healthy as i32);
+        let status = reconcile::app_status("web", &target, &containers, None);
+        assert_eq!(status.ready_replicas, Some(2));
+        assert_eq!(status.converged, Some(true));
+
+        containers.push(replica("c", "web", "old", 0));
+        let status = reconcile::app_status("web", &target, &containers, None);
+        assert_eq!(status.converged, Some(false));
+    }
+
+    #[test]
+    fn validate_rejects_bad_apps() {
+        let app = |app_name: &str| DesiredApp {
+            app_name: Some(app_name.to_string()),
+            run: Some(run_params()),
+            replicas: Some(1),
+            routes: vec![],
+        };
+        let state = |apps: Vec<DesiredApp>| DesiredState {
+            generation: Some(1),
+            apps,
+        };
+
+        assert!(reconcile::validate(&state(vec![app("web"), app("worker")])).is_ok());
+
+        let port_map = |to: &str| PortMap {
+            from: Some("80".into()),
+            to: Some(to.into()),
+            host_ip: None,
+        };
+        let mut fixed_port = app("web");
+        fixed_port.run.as_mut().unwrap().container_host_config = Some(ContainerHostConfig {
+            port_maps: vec![port_map("8080")],
+            ..Default::default()
+        });
+        assert!(reconcile::validate(&state(vec![fixed_port.clone()])).is_ok());
+
+        let mut random_ports = fixed_port.clone();
+        random_ports.replicas = Some(3);
+        random_ports.run.as_mut().unwrap().container_host_config = Some(ContainerHostConfig {
+            port_maps: vec![port_map("")],
+            ..Default::default()
+        });
+        assert!(reconcile::validate(&state(vec![random_ports])).is_ok());
+
+        fixed_port.replicas = Some(2);
+
+        let mut no_run = app("web");
+        no_run.run = None;
+        let mut too_many = app("web");
+        too_many.replicas = Some(reconcile::MAX_REPLICAS + 1);
+        let mut bad_route = app("web");
+        bad_route.routes = vec![AppRoute {
+            port: Some(8080),
+            ..Default::default()
+        }];
+
+        for (case, apps) in [
+            ("a duplicate app", vec![app("web"), app("web")]),
+            ("an invalid app name", vec![app("-web")]),
+            ("an app without run parameters", vec![no_run]),
+            ("too many replicas", vec![too_many]),
+            ("a route without hostname or path", vec![bad_route]),
+            ("replicas sharing a fixed host port", vec![fixed_port]),
+        ] {
+            assert!(
+                reconcile::validate(&state(apps)).is_err(),
+                "{case} should be rejected"
+            );
+        }
+    }
+}
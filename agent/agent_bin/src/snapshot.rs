@@ -0,0 +1,360 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use agent_wire::deploything::v1::{AgentSnapshot, AgentSnapshotDelta, ContainerStatus};
+use bollard::Docker;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, instrument};
+
+use crate::{
+    docker_api::{self, InspectCache, StatsCache},
+    ws::encode_message,
+};
+
+/// How long to wait after a snapshot is requested before building it, so that a burst of
+/// events (e.g. a container dying and restarting) results in a single snapshot.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Requests an immediate snapshot from a `SnapshotUpdater`.
+#[derive(Debug, Clone)]
+pub struct SnapshotTrigger {
+    tx: Sender<()>,
+    full: Arc<AtomicBool>,
+}
+
+impl SnapshotTrigger {
+    /// Requests a delta against the last snapshot sent. Requests made while one is already
+    /// pending are coalesced.
+    pub fn request(&self) {
+        let _ = self.tx.try_send(());
+    }
+
+    /// Requests a full snapshot rather than a delta.
+    pub fn request_full(&self) {
+        self.full.store(true, Ordering::SeqCst);
+        self.request();
+    }
+}
+
+/// Receives the requests made through a `SnapshotTrigger`.
+pub struct SnapshotRequests {
+    rx: Receiver<()>,
+    full: Arc<AtomicBool>,
+}
+
+/// Creates a trigger and the receiving end to hand to a `SnapshotUpdater`.
+pub fn trigger() -> (SnapshotTrigger, SnapshotRequests) {
+    let (tx, rx) = mpsc::channel(1);
+    let full = Arc::new(AtomicBool::new(false));
+
+    let trigger = SnapshotTrigger {
+        tx,
+        full: full.clone(),
+    };
+
+    (trigger, SnapshotRequests { rx, full })
+}
+
+/// Sends snapshot deltas to the control plane whenever one is requested, and a full
+/// snapshot every `interval` so that the control plane periodically resyncs. Host
+/// information is only included in full snapshots.
+pub struct SnapshotUpdater<'a> {
+    docker: &'a Docker,
+    msg_tx: Sender<Message>,
+    requests: SnapshotRequests,
+    stats: StatsCache,
+    inspected: InspectCache,
+    full_schedule: FullSchedule,
+    include_unmanaged: bool,
+    sent: SentState,
+    started_at: Instant,
+}
+
+impl<'a> SnapshotUpdater<'a> {
+    pub fn new(
+        docker: &'a Docker,
+        msg_tx: Sender<Message>,
+        requests: SnapshotRequests,
+        stats: StatsCache,
+        interval: Duration,
+        include_unmanaged: bool,
+    ) -> Self {
+        Self {
+            docker,
+            msg_tx,
+            requests,
+            stats,
+            inspected: InspectCache::new(),
+            full_schedule: FullSchedule::new(interval, Instant::now()),
+            include_unmanaged,
+            sent: SentState::default(),
+            started_at: Instant::now(),
+        }
+    }
+
+    pub async fn run(&mut self) {
+        loop {
+            let until_full = self.full_schedule.until_due(Instant::now());
+
+            tokio::select! {
+                _ = tokio::time::sleep(until_full) => {}
+                Some(()) = self.requests.rx.recv() => {
+                    tokio::time::sleep(DEBOUNCE).await;
+                    while self.requests.rx.try_recv().is_ok() {}
+                }
+            }
+
+            let now = Instant::now();
+            let full = self.requests.full.swap(false, Ordering::SeqCst)
+                || self.full_schedule.is_due(now)
+                || self.sent.sequence == 0;
+            if full {
+                self.full_schedule.attempted(now);
+            }
+
+            if !self.send_snapshot(full).await {
+                error!("Message channel closed, no longer sending snapshots");
+                return;
+            }
+        }
+    }
+
+    /// Returns `false` if the message channel has closed.
+    #[instrument(skip(self))]
+    async fn send_snapshot(&mut self, full: bool) -> bool {
+        let snapshot = docker_api::build_snapshot(
+            self.docker,
+            self.include_unmanaged,
+            &self.stats,
+            &mut self.inspected,
+        )
+        .await;
+
+        let mut snapshot = match snapshot {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                error!("Failed to build snapshot: {e}");
+                return true;
+            }
+        };
+
+        let message = if full || self.sent.sequence == 0 {
+            let host = docker_api::host_info(self.docker, self.started_at.elapsed()).await;
+            snapshot.host = Some(host);
+            encode_message(self.sent.full(snapshot))
+        } else {
+            match self.sent.delta(snapshot) {
+                Some(delta) => encode_message(delta),
+                None => return true,
+            }
+        };
+
+        self.msg_tx.send(message).await.is_ok()
+    }
+}
+
+/// When the next full snapshot is due, so that the control plane resyncs every `interval`
+/// however often deltas are sent.
+#[derive(Debug)]
+struct FullSchedule {
+    interval: Duration,
+    last: Instant,
+}
+
+impl FullSchedule {
+    fn new(interval: Duration, now: Instant) -> Self {
+        Self {
+            interval,
+            last: now,
+        }
+    }
+
+    fn until_due(&self, now: Instant) -> Duration {
+        (self.last + self.interval).saturating_duration_since(now)
+    }
+
+    fn is_due(&self, now: Instant) -> bool {
+        self.until_due(now).is_zero()
+    }
+
+    /// Records a full snapshot, whether or not it could be built, so that a failing one is
+    /// retried after `interval` rather than straight away.
+    fn attempted(&mut self, now: Instant) {
+        self.last = now;
+    }
+}
+
+/// What has been sent to the control plane, for computing deltas against.
+#[derive(Debug, Default)]
+struct SentState {
+    sequence: u64,
+    containers: HashMap<String, ContainerStatus>,
+}
+
+impl SentState {
+    fn full(&mut self, mut snapshot: AgentSnapshot) -> AgentSnapshot {
+        self.sequence += 1;
+        snapshot.sequence = Some(self.sequence);
+
+        self.containers = snapshot
+            .container_status
+            .iter()
+            .filter_map(|status| Some((status.id.clone()?, status.clone())))
+            .collect();
+
+        snapshot
+    }
+
+    /// Returns `None` if nothing has changed since the last snapshot or delta.
+    fn delta(&mut self, snapshot: AgentSnapshot) -> Option<AgentSnapshotDelta> {
+        let mut previous = std::mem::take(&mut self.containers);
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+
+        for status in snapshot.container_status {
+            let Some(id) = status.id.clone() else {
+                continue;
+            };
+
+            match previous.remove(&id) {
+                None => added.push(status.clone()),
+                Some(sent) if !unchanged(&sent, &status) => changed.push(status.clone()),
+                Some(_) => {}
+            }
+
+            self.containers.insert(id, status);
+        }
+
+        let mut removed: Vec<_> = previous.into_keys().collect();
+        removed.sort();
+
+        if added.is_empty() && changed.is_empty() && removed.is_empty() {
+            return None;
+        }
+
+        let base_sequence = self.sequence;
+        self.sequence += 1;
+
+        Some(AgentSnapshotDelta {
+            sequence: Some(self.sequence),
+            base_sequence: Some(base_sequence),
+            timestamp: snapshot.timestamp,
+            added,
+            changed,
+            removed,
+        })
+    }
+}
+
+/// Compares statuses, ignoring fields that change with the passage of time alone.
+fn unchanged(a: &ContainerStatus, b: &ContainerStatus) -> bool {
+    let strip = |status: &ContainerStatus| ContainerStatus {
+        uptime: None,
+        status_text: None,
+        stats: None,
+        ..status.clone()
+    };
+
+    strip(a) == strip(b)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use agent_wire::deploything::v1::{AgentSnapshot, ContainerState, ContainerStatus};
+    use prost_types::Duration;
+
+    use crate::snapshot::{FullSchedule, SentState};
+
+    fn status(id: &str, state: ContainerState) -> ContainerStatus {
+        ContainerStatus {
+            id: Some(id.to_string()),
+            container_state: Some(state as i32),
+            ..Default::default()
+        }
+    }
+
+    fn snapshot(containers: &[ContainerStatus]) -> AgentSnapshot {
+        AgentSnapshot {
+            container_status: containers.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn delta_reports_added_changed_and_removed() {
+        let mut sent = SentState::default();
+        let full = sent.full(snapshot(&[
+            status("a", ContainerState::Running),
+            status("b", ContainerState::Running),
+        ]));
+        assert_eq!(full.sequence, Some(1));
+
+        let delta = sent
+            .delta(snapshot(&[
+                status("a", ContainerState::Exited),
+                status("c", ContainerState::Running),
+            ]))
+            .unwrap();
+
+        assert_eq!(delta.base_sequence, Some(1));
+        assert_eq!(delta.sequence, Some(2));
+        assert_eq!(delta.added, vec![status("c", ContainerState::Running)]);
+        assert_eq!(delta.changed, vec![status("a", ContainerState::Exited)]);
+        assert_eq!(delta.removed, vec!["b".to_string()]);
+
+        let delta = sent
+            .delta(snapshot(&[status("c", ContainerState::Running)]))
+            .unwrap();
+        assert_eq!(delta.base_sequence, Some(2));
+        assert_eq!(delta.removed, vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn no_delta_without_changes() {
+        let mut sent = SentState::default();
+        sent.full(snapshot(&[status("a", ContainerState::Running)]));
+
+        let mut later = status("a", ContainerState::Running);
+        later.uptime = Some(Duration {
+            seconds: 60,
+            nanos: 0,
+        });
+        later.status_text = Some("Up About a minute".to_string());
+
+        assert!(sent.delta(snapshot(&[later])).is_none());
+        assert_eq!(sent.sequence, 1);
+    }
+
+    #[test]
+    fn full_snapshot_is_due_despite_frequent_deltas() {
+        let interval = std::time::Duration::from_secs(60);
+        let start = Instant::now();
+        let mut schedule = FullSchedule::new(interval, start);
+
+        // Deltas requested every ten seconds do not put off the next full snapshot.
+        for secs in (10..60).step_by(10) {
+            let now = start + std::time::Duration::from_secs(secs);
+            assert!(!schedule.is_due(now));
+            assert_eq!(
+                schedule.until_due(now),
+                std::time::Duration::from_secs(60 - secs)
+            );
+        }
+
+        let now = start + interval;
+        assert!(schedule.is_due(now));
+
+        schedule.attempted(now);
+        assert!(!schedule.is_due(now));
+        assert!(schedule.is_due(now + interval));
+    }
+}
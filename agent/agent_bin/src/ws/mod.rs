@@ -1,3 +1,13 @@
+use agent_wire::deploything::v1::AgentMessage;
+use prost::Message as _;
+use tokio_tungstenite::tungstenite::Message;
+
 pub mod errors;
 pub mod receiver;
 pub mod sender;
+
+/// Wraps `message` in an `AgentMessage` envelope and encodes it as a binary websocket frame.
+pub fn encode_message(message: impl Into<AgentMessage>) -> Message {
+    let message: AgentMessage = message.into();
+    Message::Binary(message.encode_to_vec().into())
+}
@@ -1,9 +1,13 @@
+use agent_wire::deploything::v1::RemoteCommand;
 use futures_util::{Stream, StreamExt};
 use tokio::sync::{mpsc::Sender, oneshot};
 use tokio_tungstenite::tungstenite::{self, Message};
 use tracing::{error, info, instrument};
 
-use crate::{cmd::CommandBundle, ws::errors::WsError};
+use crate::{
+    cmd::CommandBundle,
+    ws::{encode_message, errors::WsError},
+};
 
 type StreamItem = Result<Message, tungstenite::Error>;
 
@@ -49,7 +53,8 @@ where
             unimplemented!();
         };
 
-        let cmd = prost::Message::decode(bytes)?;
+        let cmd: RemoteCommand = prost::Message::decode(bytes)?;
+        let command_id = cmd.command_id.clone();
 
         let (response_tx, response_rx) = oneshot::channel();
         let cmd_bundle = CommandBundle::new(cmd, response_tx);
@@ -57,8 +62,10 @@ where
         self.cmd_tx.send(cmd_bundle).await?;
 
         match response_rx.await {
-            Ok(_) => {
+            Ok(response) => {
                 info!("Command executed successfully");
+                let result = response.into_result(command_id);
+                self.msg_tx.send(encode_message(result)).await?;
             }
             Err(e) => {
                 error!("Command execution failed: {e}");
@@ -11,18 +11,31 @@ where
 {
     sink: S,
     msg_rx: Receiver<Message>,
+    /// Only sent when there is nothing waiting in `msg_rx`.
+    bulk_rx: Receiver<Message>,
 }
 
 impl<S> WsSender<S>
 where
     S: Sink<Message, Error = tungstenite::Error> + Unpin,
 {
-    pub fn new(sink: S, msg_rx: Receiver<Message>) -> Self {
-        Self { sink, msg_rx }
+    pub fn new(sink: S, msg_rx: Receiver<Message>, bulk_rx: Receiver<Message>) -> Self {
+        Self {
+            sink,
+            msg_rx,
+            bulk_rx,
+        }
     }
 
     pub async fn handle(&mut self) -> Result<(), WsError> {
-        while let Some(message) = self.msg_rx.recv().await {
+        loop {
+            let message = tokio::select! {
+                biased;
+                Some(message) = self.msg_rx.recv() => message,
+                Some(message) = self.bulk_rx.recv() => message,
+                else => break,
+            };
+
             if let Err(e) = self.sink.send(message).await {
                 error!("Failed to send message to control plane: {e}");
             }
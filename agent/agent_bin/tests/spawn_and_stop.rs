@@ -1,28 +1,30 @@
 use std::time::Duration;
 
 use agent_bin::docker_api;
-use agent_wire::deploything::v1::{ContainerHostConfig, PortMap};
+use agent_wire::deploything::v1::{ContainerHostConfig, PortMap, RunParams};
 use bollard::Docker;
 
 #[tokio::test]
 async fn spawn_and_stop() {
     let docker = Docker::connect_with_defaults().unwrap();
 
-    let host_config = Some(ContainerHostConfig {
-        port_map: Some(PortMap {
-            from: Some("8080/tcp".into()),
-            to: Some("8080".into()),
+    let params = RunParams {
+        image_name: Some("mccutchen/go-httpbin".into()),
+        tag: Some("latest".into()),
+        container_host_config: Some(ContainerHostConfig {
+            port_maps: vec![PortMap {
+                from: Some("8080/tcp".into()),
+                to: Some("8080".into()),
+                ..Default::default()
+            }],
+            ..Default::default()
         }),
-    });
+        ..Default::default()
+    };
 
-    let container = docker_api::Container::spawn_from_image(
-        &docker,
-        "mccutchen/go-httpbin",
-        "latest",
-        host_config.as_ref(),
-    )
-    .await
-    .unwrap();
+    let container = docker_api::Container::spawn_from_image(&docker, &params)
+        .await
+        .unwrap();
 
     tokio::time::sleep(Duration::from_secs(1)).await;
 
@@ -32,5 +34,8 @@ async fn spawn_and_stop() {
 
     assert_eq!(200, resp.status());
 
-    container.stop().await.unwrap();
+    container
+        .stop(&docker_api::StopOptions::default())
+        .await
+        .unwrap();
 }
@@ -1,2 +1,2 @@
-mod route;
+pub mod route;
 pub mod server;
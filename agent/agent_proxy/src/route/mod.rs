@@ -1,23 +1,63 @@
 mod matcher;
 mod table;
 
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
 pub use matcher::*;
 pub use table::*;
 
 /// `Service` describes an upstream application that can be routed to.
-/// Right now, all we care about is the port - we assume that all applications
-/// run on the same host as the agent.
+/// Services reached through a published host port live on the agent's host, while
+/// containers on a managed network are reached directly at their network address.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Service {
     name: String,
+    host: IpAddr,
     port: u16,
 }
 
 impl Service {
+    /// A service published on `port` of the agent's host.
     pub fn new(name: &str, port: u16) -> Self {
+        Self::at(name, IpAddr::V4(Ipv4Addr::LOCALHOST), port)
+    }
+
+    /// A service listening on `port` at `host`, e.g. a container's address on a managed network.
+    pub fn at(name: &str, host: IpAddr, port: u16) -> Self {
         Self {
             name: name.to_string(),
+            host,
             port,
         }
     }
+
+    /// The address requests for this service should be proxied to.
+    pub fn address(&self) -> SocketAddr {
+        SocketAddr::new(self.host, self.port)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    use crate::route::Service;
+
+    #[test]
+    fn service_defaults_to_localhost() {
+        let svc = Service::new("web", 8080);
+        assert_eq!(
+            svc.address(),
+            "127.0.0.1:8080".parse::<SocketAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn service_at_network_address() {
+        let svc = Service::at("web", IpAddr::V4(Ipv4Addr::new(172, 18, 0, 2)), 80);
+        assert_eq!(
+            svc.address(),
+            "172.18.0.2:80".parse::<SocketAddr>().unwrap()
+        );
+    }
 }
@@ -2,36 +2,72 @@ use std::sync::{Arc, RwLock};
 
 use axum::{
     Router,
-    extract::{OriginalUri, State},
-    http::{HeaderMap, StatusCode},
-    response::IntoResponse,
-    routing::any,
+    body::Body,
+    extract::{Request, State},
+    http::{
+        HeaderMap, HeaderName, StatusCode, Uri,
+        header::{CONNECTION, HOST},
+    },
+    response::{IntoResponse, Response},
+};
+use hyper_util::{
+    client::legacy::{Client, connect::HttpConnector},
+    rt::TokioExecutor,
 };
 use tokio::net::TcpListener;
 use tracing::{info, warn};
 
-use crate::route::RouteTable;
+use crate::route::{RouteTable, Service};
+
+/// Headers that only apply to a single connection, so they are not passed between the
+/// client and the upstream service.
+const HOP_BY_HOP_HEADERS: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
 
 /// `ReverseProxy` is a simple web server that accepts any incoming request,
 /// and uses a set of rules to decide where to proxy that request.
-#[derive(Debug)]
-pub struct ReverseProxy;
+#[derive(Debug, Default)]
+pub struct ReverseProxy {
+    state: ProxyState,
+}
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone)]
 pub struct ProxyState {
     route_table: Arc<RwLock<RouteTable>>,
+    client: Client<HttpConnector, Body>,
+}
+
+impl Default for ProxyState {
+    fn default() -> Self {
+        Self {
+            route_table: Arc::default(),
+            client: Client::builder(TokioExecutor::new()).build_http(),
+        }
+    }
 }
 
 impl ReverseProxy {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// The table requests are routed by. Changes apply from the next request onwards.
+    pub fn route_table(&self) -> Arc<RwLock<RouteTable>> {
+        self.state.route_table.clone()
     }
 
     pub async fn serve(&self, listener: TcpListener) {
-        let state = ProxyState::default();
         let app = Router::new()
-            .route("/{*rest}", any(proxy_request))
-            .with_state(state);
+            .fallback(proxy_request)
+            .with_state(self.state.clone());
 
         info!(
             "Reverse-proxy listening on port {}",
@@ -42,24 +78,182 @@ impl ReverseProxy {
     }
 }
 
-async fn proxy_request(
-    uri: OriginalUri,
-    headers: HeaderMap,
-    State(state): State<ProxyState>,
-) -> impl IntoResponse {
-    let rt = state.route_table.read().unwrap();
+/// Forwards the request to the service its hostname and path are routed to. Responds with
+/// `404 Not Found` if no route matches, and `502 Bad Gateway` if the service cannot be
+/// reached.
+async fn proxy_request(State(state): State<ProxyState>, mut request: Request) -> Response {
+    let hostname = request
+        .headers()
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .map(strip_port)
+        .unwrap_or_default();
+    let path = request.uri().path();
+
+    let service = state.route_table.read().unwrap().route(hostname, path);
 
-    let hostname = headers.get("host").unwrap().to_str().unwrap();
-    let path = uri.to_string();
+    let Some(service) = service else {
+        warn!("No suitable route entry found for {hostname}{path}");
+        return (StatusCode::NOT_FOUND, "No route matches the request").into_response();
+    };
+
+    let Some(uri) = upstream_uri(&service, request.uri()) else {
+        return (StatusCode::BAD_REQUEST, "Invalid request URI").into_response();
+    };
 
-    match rt.route(hostname, &path) {
-        Some(svc) => {
-            info!("Routing request to {svc:?}");
+    info!("Routing request to {service:?} at {uri}");
+    *request.uri_mut() = uri;
+    remove_hop_by_hop_headers(request.headers_mut());
+
+    match state.client.request(request).await {
+        Ok(response) => {
+            let mut response = response.map(Body::new);
+            remove_hop_by_hop_headers(response.headers_mut());
+            response
         }
-        None => {
-            warn!("No suitable route entry found.")
+        Err(e) => {
+            warn!("Failed to proxy request to {service:?}: {e}");
+            (
+                StatusCode::BAD_GATEWAY,
+                "The upstream service is unavailable",
+            )
+                .into_response()
         }
+    }
+}
+
+/// The `Host` header may include the port the proxy was reached on, which routes ignore.
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((hostname, port)) if port.bytes().all(|b| b.is_ascii_digit()) => hostname,
+        _ => host,
+    }
+}
+
+fn upstream_uri(service: &Service, uri: &Uri) -> Option<Uri> {
+    let path_and_query = uri.path_and_query().map_or("/", |pq| pq.as_str());
+    format!("http://{}{path_and_query}", service.address())
+        .parse()
+        .ok()
+}
+
+fn remove_hop_by_hop_headers(headers: &mut HeaderMap) {
+    // Headers named by `Connection` are also specific to the connection.
+    let named: Vec<HeaderName> = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| name.trim().parse().ok())
+        .collect();
+
+    for name in named
+        .iter()
+        .chain(&HOP_BY_HOP_HEADERS.map(HeaderName::from_static))
+    {
+        headers.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+
+    use axum::{
+        Router,
+        body::{self, Body},
+        http::{HeaderMap, Request, StatusCode, Uri, header::HOST},
+        response::Response,
+    };
+    use hyper_util::{client::legacy::Client, rt::TokioExecutor};
+    use tokio::net::TcpListener;
+
+    use crate::{
+        route::{RouteMatchBuilder, Service},
+        server::{ReverseProxy, proxy::strip_port},
     };
 
-    (StatusCode::OK, "Hello world!")
+    /// Serves `app` on an unused local port, returning its address.
+    async fn serve(app: Router) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        address
+    }
+
+    /// Starts a proxy that routes `example.com` to `port`.
+    async fn proxy_to(port: u16) -> SocketAddr {
+        let proxy = ReverseProxy::new();
+        proxy.route_table().write().unwrap().add(
+            RouteMatchBuilder::new().hostname("example.com").build(),
+            Service::new("web", port),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { proxy.serve(listener).await });
+        address
+    }
+
+    async fn get(proxy: SocketAddr, host: &str, path: &str) -> (StatusCode, String) {
+        let client = Client::builder(TokioExecutor::new()).build_http::<Body>();
+        let request = Request::get(format!("http://{proxy}{path}"))
+            .header(HOST, host)
+            .body(Body::empty())
+            .unwrap();
+
+        let response = client.request(request).await.unwrap();
+        let status = response.status();
+        let body = body::to_bytes(Body::new(response.into_body()), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn forwards_request_to_routed_service() {
+        let upstream = Router::new().fallback(async |uri: Uri, headers: HeaderMap| {
+            let host = headers[HOST].to_str().unwrap().to_string();
+            Response::new(Body::from(format!("{host} {uri}")))
+        });
+        let upstream = serve(upstream).await;
+        let proxy = proxy_to(upstream.port()).await;
+
+        assert_eq!(
+            get(proxy, "example.com:3000", "/hello?name=world").await,
+            (
+                StatusCode::OK,
+                "example.com:3000 /hello?name=world".to_string()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn responds_not_found_without_matching_route() {
+        let proxy = proxy_to(1).await;
+
+        let (status, _) = get(proxy, "example.org", "/").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn responds_bad_gateway_when_service_is_unreachable() {
+        // Nothing listens on the port once the listener is dropped.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let proxy = proxy_to(port).await;
+
+        let (status, _) = get(proxy, "example.com", "/").await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+    }
+
+    #[test]
+    fn host_port_is_ignored() {
+        assert_eq!(strip_port("example.com:3000"), "example.com");
+        assert_eq!(strip_port("example.com"), "example.com");
+        assert_eq!(strip_port("[::1]:3000"), "[::1]");
+        assert_eq!(strip_port("[::1]"), "[::1]");
+    }
 }
@@ -1,10 +1,17 @@
 fn main() {
-    prost_build::compile_protos(
-        &[
-            "../../protos/deploything/v1/remote_command.proto",
-            "../../protos/deploything/v1/agent_snapshot.proto",
-        ],
-        &["../../protos"],
-    )
-    .unwrap();
+    // The protos live outside this crate, so cargo won't notice changes to them by default.
+    println!("cargo:rerun-if-changed=../../protos");
+
+    prost_build::Config::new()
+        // `Debug` for secrets is implemented by hand in `impls`, so their values are never logged.
+        .skip_debug([".deploything.v1.SecretValue"])
+        .compile_protos(
+            &[
+                "../../protos/deploything/v1/remote_command.proto",
+                "../../protos/deploything/v1/agent_snapshot.proto",
+                "../../protos/deploything/v1/agent_message.proto",
+            ],
+            &["../../protos"],
+        )
+        .unwrap();
 }
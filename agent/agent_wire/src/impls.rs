@@ -1,20 +1,101 @@
-use bollard::secret::{ContainerSummary, ContainerSummaryStateEnum};
+use std::time::SystemTime;
 
-use crate::deploything::v1::{ContainerState, ContainerStatus};
+use bollard::secret::{
+    ContainerCpuStats, ContainerMemoryStats, ContainerStateStatusEnum, ContainerStatsResponse,
+    ContainerSummary, ContainerSummaryStateEnum, HealthStatusEnum, MountPoint, MountPointTypeEnum,
+    Port,
+};
+use prost_types::{Duration, Timestamp};
+
+use crate::deploything::v1::{
+    AgentMessage, AgentSnapshot, AgentSnapshotDelta, ArchiveChunk, ArchiveDownloadEnded,
+    ArchiveUploadResult, BuildLog, BuildResult, CommandResult, ContainerEvent, ContainerHealth,
+    ContainerMount, ContainerNetwork, ContainerState, ContainerStats, ContainerStatus, ExecOutput,
+    ExecResult, LogFrame, LogSubscriptionEnded, PublishedPort, ReconcileStatus, SecretValue,
+    Volume, agent_message,
+};
 
 impl From<&ContainerSummary> for ContainerStatus {
     fn from(summary: &ContainerSummary) -> Self {
-        let summary = summary.clone();
-        let name = summary.names.map(|names| names.first().unwrap().clone());
+        let name = summary
+            .names
+            .as_ref()
+            .and_then(|names| names.first())
+            .cloned();
         let state = summary
             .state
             .map(|state| ContainerState::from(state) as i32);
+        let created_at = summary
+            .created
+            .map(|seconds| Timestamp { seconds, nanos: 0 });
+
+        let ports = summary
+            .ports
+            .iter()
+            .flatten()
+            .filter_map(published_port)
+            .collect();
+
+        let mut networks: Vec<_> = summary
+            .network_settings
+            .iter()
+            .flat_map(|settings| settings.networks.iter().flatten())
+            .map(|(name, endpoint)| ContainerNetwork {
+                name: Some(name.clone()),
+                network_id: endpoint.network_id.clone(),
+                ip_address: endpoint.ip_address.clone().filter(|ip| !ip.is_empty()),
+            })
+            .collect();
+        networks.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mounts = summary
+            .mounts
+            .iter()
+            .flatten()
+            .map(ContainerMount::from)
+            .collect();
 
         ContainerStatus {
-            id: summary.id,
-            name: name,
-            image_id: summary.image_id,
+            id: summary.id.clone(),
+            name,
+            image_id: summary.image_id.clone(),
             container_state: state,
+            labels: summary.labels.clone().unwrap_or_default(),
+            image: summary.image.clone(),
+            created_at,
+            ports,
+            networks,
+            mounts,
+            status_text: summary.status.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Only ports that are published on the host are reported.
+fn published_port(port: &Port) -> Option<PublishedPort> {
+    let host_port = port.public_port?;
+    let protocol = port.typ.map_or("tcp".to_string(), |typ| typ.to_string());
+
+    Some(PublishedPort {
+        container_port: Some(format!("{}/{protocol}", port.private_port)),
+        host_ip: port.ip.clone(),
+        host_port: Some(host_port as u32),
+    })
+}
+
+impl From<&MountPoint> for ContainerMount {
+    fn from(mount: &MountPoint) -> Self {
+        let source = match mount.typ {
+            Some(MountPointTypeEnum::VOLUME) => mount.name.clone(),
+            _ => mount.source.clone(),
+        };
+
+        ContainerMount {
+            r#type: mount.typ.map(|typ| typ.to_string()),
+            source,
+            target: mount.destination.clone(),
+            read_only: mount.rw.map(|rw| !rw),
         }
     }
 }
@@ -22,9 +103,570 @@ impl From<&ContainerSummary> for ContainerStatus {
 impl From<ContainerSummaryStateEnum> for ContainerState {
     fn from(state: ContainerSummaryStateEnum) -> Self {
         match state {
+            ContainerSummaryStateEnum::CREATED => ContainerState::Created,
             ContainerSummaryStateEnum::RUNNING => ContainerState::Running,
+            ContainerSummaryStateEnum::PAUSED => ContainerState::Paused,
+            ContainerSummaryStateEnum::RESTARTING => ContainerState::Restarting,
+            ContainerSummaryStateEnum::REMOVING => ContainerState::Removing,
             ContainerSummaryStateEnum::EXITED => ContainerState::Exited,
-            _ => ContainerState::Unspecified,
+            ContainerSummaryStateEnum::DEAD => ContainerState::Dead,
+            ContainerSummaryStateEnum::EMPTY => ContainerState::Unspecified,
+        }
+    }
+}
+
+impl From<ContainerStateStatusEnum> for ContainerState {
+    fn from(state: ContainerStateStatusEnum) -> Self {
+        match state {
+            ContainerStateStatusEnum::CREATED => ContainerState::Created,
+            ContainerStateStatusEnum::RUNNING => ContainerState::Running,
+            ContainerStateStatusEnum::PAUSED => ContainerState::Paused,
+            ContainerStateStatusEnum::RESTARTING => ContainerState::Restarting,
+            ContainerStateStatusEnum::REMOVING => ContainerState::Removing,
+            ContainerStateStatusEnum::EXITED => ContainerState::Exited,
+            ContainerStateStatusEnum::DEAD => ContainerState::Dead,
+            ContainerStateStatusEnum::EMPTY => ContainerState::Unspecified,
+        }
+    }
+}
+
+impl ContainerStatus {
+    /// Fills in the details only available from inspecting the container. A state that
+    /// was already listed is kept, since the inspected state may be older.
+    pub fn apply_inspected_state(&mut self, state: &bollard::secret::ContainerState) {
+        if let Some(status) = state.status
+            && self.container_state.is_none()
+        {
+            self.container_state = Some(ContainerState::from(status) as i32);
+        }
+
+        let exited = matches!(
+            state.status,
+            Some(ContainerStateStatusEnum::EXITED | ContainerStateStatusEnum::DEAD)
+        );
+
+        let started = matches!(
+            state.status,
+            Some(ContainerStateStatusEnum::RUNNING | ContainerStateStatusEnum::PAUSED)
+        );
+
+        if started {
+            self.started_at = state
+                .started_at
+                .as_ref()
+                .and_then(|started_at| started_at.parse::<Timestamp>().ok());
+        }
+
+        if exited {
+            self.exit_code = state.exit_code;
+            self.finished_at = state
+                .finished_at
+                .as_ref()
+                .and_then(|finished_at| finished_at.parse::<Timestamp>().ok());
+        }
+    }
+
+    /// Sets the uptime as of `now` for containers that have started.
+    pub fn set_uptime(&mut self, now: SystemTime) {
+        self.uptime = self
+            .started_at
+            .and_then(|started_at| SystemTime::try_from(started_at).ok())
+            .and_then(|started_at| now.duration_since(started_at).ok())
+            .and_then(|uptime| Duration::try_from(uptime).ok());
+    }
+}
+
+impl From<HealthStatusEnum> for ContainerHealth {
+    fn from(health: HealthStatusEnum) -> Self {
+        match health {
+            HealthStatusEnum::NONE => ContainerHealth::None,
+            HealthStatusEnum::STARTING => ContainerHealth::Starting,
+            HealthStatusEnum::HEALTHY => ContainerHealth::Healthy,
+            HealthStatusEnum::UNHEALTHY => ContainerHealth::Unhealthy,
+            HealthStatusEnum::EMPTY => ContainerHealth::Unspecified,
+        }
+    }
+}
+
+impl From<&ContainerStatsResponse> for ContainerStats {
+    fn from(stats: &ContainerStatsResponse) -> Self {
+        let (network_rx_bytes, network_tx_bytes) = stats
+            .networks
+            .iter()
+            .flat_map(|networks| networks.values())
+            .fold((0, 0), |(rx, tx), network| {
+                (
+                    rx + network.rx_bytes.unwrap_or(0),
+                    tx + network.tx_bytes.unwrap_or(0),
+                )
+            });
+
+        let (block_read_bytes, block_write_bytes) = stats
+            .blkio_stats
+            .iter()
+            .flat_map(|blkio| blkio.io_service_bytes_recursive.iter().flatten())
+            .fold((0, 0), |(read, write), entry| {
+                let value = entry.value.unwrap_or(0);
+                match entry.op.as_deref().map(str::to_ascii_lowercase).as_deref() {
+                    Some("read") => (read + value, write),
+                    Some("write") => (read, write + value),
+                    _ => (read, write),
+                }
+            });
+
+        ContainerStats {
+            cpu_percent: cpu_percent(stats),
+            memory_usage_bytes: stats.memory_stats.as_ref().and_then(memory_usage),
+            memory_limit_bytes: stats.memory_stats.as_ref().and_then(|memory| memory.limit),
+            network_rx_bytes: Some(network_rx_bytes),
+            network_tx_bytes: Some(network_tx_bytes),
+            block_read_bytes: Some(block_read_bytes),
+            block_write_bytes: Some(block_write_bytes),
+            collected_at: stats
+                .read
+                .as_ref()
+                .and_then(|read| read.parse::<Timestamp>().ok()),
+        }
+    }
+}
+
+/// CPU usage since the previous sample, calculated the same way as `docker stats`.
+fn cpu_percent(stats: &ContainerStatsResponse) -> Option<f64> {
+    let cpu = stats.cpu_stats.as_ref()?;
+    let precpu = stats.precpu_stats.as_ref()?;
+
+    let total =
+        |cpu: &ContainerCpuStats| cpu.cpu_usage.as_ref().and_then(|usage| usage.total_usage);
+    let cpu_delta = total(cpu)?.checked_sub(total(precpu).unwrap_or(0))?;
+    let system_delta = cpu
+        .system_cpu_usage?
+        .checked_sub(precpu.system_cpu_usage.unwrap_or(0))?;
+
+    if system_delta == 0 {
+        return Some(0.0);
+    }
+
+    let online_cpus = cpu.online_cpus.map(u64::from).or_else(|| {
+        cpu.cpu_usage
+            .as_ref()
+            .and_then(|usage| usage.percpu_usage.as_ref())
+            .map(|percpu| percpu.len() as u64)
+    })?;
+
+    Some(cpu_delta as f64 / system_delta as f64 * online_cpus as f64 * 100.0)
+}
+
+/// Memory usage without the inactive page cache, which the kernel can reclaim at any time.
+fn memory_usage(memory: &ContainerMemoryStats) -> Option<u64> {
+    let usage = memory.usage?;
+    let inactive_file = memory.stats.as_ref().and_then(|stats| {
+        // cgroup v1 and v2 respectively.
+        stats
+            .get("total_inactive_file")
+            .or_else(|| stats.get("inactive_file"))
+    });
+
+    Some(usage.saturating_sub(inactive_file.copied().unwrap_or(0)))
+}
+
+impl From<&bollard::secret::Volume> for Volume {
+    fn from(volume: &bollard::secret::Volume) -> Self {
+        Volume {
+            name: Some(volume.name.clone()),
+            driver: Some(volume.driver.clone()),
+            mountpoint: Some(volume.mountpoint.clone()),
+            created_at: volume
+                .created_at
+                .as_ref()
+                .and_then(|created_at| created_at.parse::<Timestamp>().ok()),
+        }
+    }
+}
+
+impl From<AgentSnapshot> for AgentMessage {
+    fn from(snapshot: AgentSnapshot) -> Self {
+        AgentMessage {
+            message: Some(agent_message::Message::Snapshot(snapshot)),
         }
     }
 }
+
+impl From<AgentSnapshotDelta> for AgentMessage {
+    fn from(delta: AgentSnapshotDelta) -> Self {
+        AgentMessage {
+            message: Some(agent_message::Message::SnapshotDelta(delta)),
+        }
+    }
+}
+
+impl From<LogFrame> for AgentMessage {
+    fn from(frame: LogFrame) -> Self {
+        AgentMessage {
+            message: Some(agent_message::Message::LogFrame(frame)),
+        }
+    }
+}
+
+impl From<LogSubscriptionEnded> for AgentMessage {
+    fn from(ended: LogSubscriptionEnded) -> Self {
+        AgentMessage {
+            message: Some(agent_message::Message::LogSubscriptionEnded(ended)),
+        }
+    }
+}
+
+impl From<ExecOutput> for AgentMessage {
+    fn from(output: ExecOutput) -> Self {
+        AgentMessage {
+            message: Some(agent_message::Message::ExecOutput(output)),
+        }
+    }
+}
+
+impl From<ExecResult> for AgentMessage {
+    fn from(result: ExecResult) -> Self {
+        AgentMessage {
+            message: Some(agent_message::Message::ExecResult(result)),
+        }
+    }
+}
+
+impl From<ArchiveChunk> for AgentMessage {
+    fn from(chunk: ArchiveChunk) -> Self {
+        AgentMessage {
+            message: Some(agent_message::Message::ArchiveChunk(chunk)),
+        }
+    }
+}
+
+impl From<ArchiveDownloadEnded> for AgentMessage {
+    fn from(ended: ArchiveDownloadEnded) -> Self {
+        AgentMessage {
+            message: Some(agent_message::Message::ArchiveDownloadEnded(ended)),
+        }
+    }
+}
+
+impl From<ArchiveUploadResult> for AgentMessage {
+    fn from(result: ArchiveUploadResult) -> Self {
+        AgentMessage {
+            message: Some(agent_message::Message::ArchiveUploadResult(result)),
+        }
+    }
+}
+
+impl From<ReconcileStatus> for AgentMessage {
+    fn from(status: ReconcileStatus) -> Self {
+        AgentMessage {
+            message: Some(agent_message::Message::ReconcileStatus(status)),
+        }
+    }
+}
+
+impl From<BuildLog> for AgentMessage {
+    fn from(log: BuildLog) -> Self {
+        AgentMessage {
+            message: Some(agent_message::Message::BuildLog(log)),
+        }
+    }
+}
+
+impl From<BuildResult> for AgentMessage {
+    fn from(result: BuildResult) -> Self {
+        AgentMessage {
+            message: Some(agent_message::Message::BuildResult(result)),
+        }
+    }
+}
+
+impl From<ContainerEvent> for AgentMessage {
+    fn from(event: ContainerEvent) -> Self {
+        AgentMessage {
+            message: Some(agent_message::Message::ContainerEvent(event)),
+        }
+    }
+}
+
+impl From<CommandResult> for AgentMessage {
+    fn from(result: CommandResult) -> Self {
+        AgentMessage {
+            message: Some(agent_message::Message::CommandResult(result)),
+        }
+    }
+}
+
+impl std::fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretValue(<redacted>)")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use std::time::SystemTime;
+
+    use bollard::secret::{
+        ContainerStateStatusEnum, ContainerStatsResponse, ContainerSummary,
+        ContainerSummaryStateEnum,
+    };
+
+    use crate::deploything::v1::{
+        ContainerMount, ContainerNetwork, ContainerState, ContainerStats, ContainerStatus,
+        PublishedPort, RunParams, SecretValue,
+    };
+
+    #[test]
+    fn every_docker_state_is_mapped() {
+        for (summary, state) in [
+            (ContainerSummaryStateEnum::CREATED, ContainerState::Created),
+            (ContainerSummaryStateEnum::RUNNING, ContainerState::Running),
+            (ContainerSummaryStateEnum::PAUSED, ContainerState::Paused),
+            (
+                ContainerSummaryStateEnum::RESTARTING,
+                ContainerState::Restarting,
+            ),
+            (
+                ContainerSummaryStateEnum::REMOVING,
+                ContainerState::Removing,
+            ),
+            (ContainerSummaryStateEnum::EXITED, ContainerState::Exited),
+            (ContainerSummaryStateEnum::DEAD, ContainerState::Dead),
+            (
+                ContainerSummaryStateEnum::EMPTY,
+                ContainerState::Unspecified,
+            ),
+        ] {
+            assert_eq!(ContainerState::from(summary), state);
+        }
+    }
+
+    /// A running container as listed by `GET /containers/json`.
+    const RUNNING_SUMMARY: &str = r#"{
+        "Id": "8dfafdbc3a40",
+        "Names": ["/web-abc123"],
+        "Image": "nginx:1.27",
+        "ImageID": "sha256:3f8a4339aadda5897b744682f5f774dc69991a81af8d715d37a616bb4c99edf5",
+        "Command": "nginx -g 'daemon off;'",
+        "Created": 1714566600,
+        "Ports": [
+            {"PrivatePort": 80, "PublicPort": 8080, "IP": "0.0.0.0", "Type": "tcp"},
+            {"PrivatePort": 443, "Type": "tcp"}
+        ],
+        "Labels": {"deployth.ing/managed": "true", "deployth.ing/app": "web"},
+        "State": "running",
+        "Status": "Up 5 minutes (healthy)",
+        "HostConfig": {"NetworkMode": "frontend"},
+        "NetworkSettings": {
+            "Networks": {
+                "frontend": {"NetworkID": "7ea29fc1412292a2", "IPAddress": "172.18.0.2"},
+                "backend": {"NetworkID": "1e3b5f0d2c6a7b8d", "IPAddress": ""}
+            }
+        },
+        "Mounts": [
+            {"Type": "volume", "Name": "web-data", "Source": "/var/lib/docker/volumes/web-data/_data", "Destination": "/data", "Driver": "local", "Mode": "z", "RW": true, "Propagation": ""},
+            {"Type": "bind", "Source": "/etc/web", "Destination": "/etc/nginx/conf.d", "Mode": "", "RW": false, "Propagation": "rprivate"}
+        ]
+    }"#;
+
+    #[test]
+    fn container_status_from_summary() {
+        let summary: ContainerSummary = serde_json::from_str(RUNNING_SUMMARY).unwrap();
+        let status = ContainerStatus::from(&summary);
+
+        assert_eq!(status.id.as_deref(), Some("8dfafdbc3a40"));
+        assert_eq!(status.name.as_deref(), Some("/web-abc123"));
+        assert_eq!(status.image.as_deref(), Some("nginx:1.27"));
+        assert_eq!(status.container_state(), ContainerState::Running);
+        assert_eq!(status.created_at.unwrap().seconds, 1_714_566_600);
+        assert_eq!(
+            status.status_text.as_deref(),
+            Some("Up 5 minutes (healthy)")
+        );
+        assert_eq!(
+            status.labels.get("deployth.ing/app").map(String::as_str),
+            Some("web")
+        );
+
+        assert_eq!(
+            status.ports,
+            vec![PublishedPort {
+                container_port: Some("80/tcp".to_string()),
+                host_ip: Some("0.0.0.0".to_string()),
+                host_port: Some(8080),
+            }]
+        );
+
+        assert_eq!(
+            status.networks,
+            vec![
+                ContainerNetwork {
+                    name: Some("backend".to_string()),
+                    network_id: Some("1e3b5f0d2c6a7b8d".to_string()),
+                    ip_address: None,
+                },
+                ContainerNetwork {
+                    name: Some("frontend".to_string()),
+                    network_id: Some("7ea29fc1412292a2".to_string()),
+                    ip_address: Some("172.18.0.2".to_string()),
+                },
+            ]
+        );
+
+        assert_eq!(
+            status.mounts,
+            vec![
+                ContainerMount {
+                    r#type: Some("volume".to_string()),
+                    source: Some("web-data".to_string()),
+                    target: Some("/data".to_string()),
+                    read_only: Some(false),
+                },
+                ContainerMount {
+                    r#type: Some("bind".to_string()),
+                    source: Some("/etc/web".to_string()),
+                    target: Some("/etc/nginx/conf.d".to_string()),
+                    read_only: Some(true),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn container_status_from_sparse_summary() {
+        let summary: ContainerSummary =
+            serde_json::from_str(r#"{"Id": "8dfafdbc3a40", "Names": []}"#).unwrap();
+        let status = ContainerStatus::from(&summary);
+
+        assert_eq!(status.id.as_deref(), Some("8dfafdbc3a40"));
+        assert_eq!(status.name, None);
+        assert!(status.ports.is_empty());
+        assert!(status.networks.is_empty());
+        assert!(status.mounts.is_empty());
+    }
+
+    #[test]
+    fn uptime_is_measured_from_start() {
+        let mut status = ContainerStatus::default();
+        status.apply_inspected_state(&bollard::secret::ContainerState {
+            status: Some(ContainerStateStatusEnum::RUNNING),
+            started_at: Some("2024-05-01T12:30:00Z".to_string()),
+            ..Default::default()
+        });
+
+        let started_at = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_714_566_600);
+        status.set_uptime(started_at + std::time::Duration::from_secs(90));
+
+        assert_eq!(status.uptime.unwrap().seconds, 90);
+    }
+
+    /// A cgroup v2 sample as returned by `GET /containers/{id}/stats?stream=false`.
+    const STATS: &str = r#"{
+        "id": "8dfafdbc3a40",
+        "name": "/web-abc123",
+        "read": "2024-05-01T12:30:00.5Z",
+        "preread": "2024-05-01T12:29:59.5Z",
+        "cpu_stats": {
+            "cpu_usage": {"total_usage": 3000000000, "usage_in_kernelmode": 0, "usage_in_usermode": 0},
+            "system_cpu_usage": 20000000000,
+            "online_cpus": 4
+        },
+        "precpu_stats": {
+            "cpu_usage": {"total_usage": 2000000000, "usage_in_kernelmode": 0, "usage_in_usermode": 0},
+            "system_cpu_usage": 16000000000,
+            "online_cpus": 4
+        },
+        "memory_stats": {
+            "usage": 73400320,
+            "limit": 536870912,
+            "stats": {"inactive_file": 10485760, "anon": 52428800}
+        },
+        "networks": {
+            "eth0": {"rx_bytes": 1000, "tx_bytes": 200},
+            "eth1": {"rx_bytes": 24, "tx_bytes": 6}
+        },
+        "blkio_stats": {
+            "io_service_bytes_recursive": [
+                {"major": 8, "minor": 0, "op": "read", "value": 4096},
+                {"major": 8, "minor": 0, "op": "write", "value": 8192},
+                {"major": 8, "minor": 16, "op": "Read", "value": 1024}
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn container_stats_from_response() {
+        let response: ContainerStatsResponse = serde_json::from_str(STATS).unwrap();
+        let stats = ContainerStats::from(&response);
+
+        assert_eq!(stats.cpu_percent, Some(100.0));
+        assert_eq!(stats.memory_usage_bytes, Some(62_914_560));
+        assert_eq!(stats.memory_limit_bytes, Some(536_870_912));
+        assert_eq!(stats.network_rx_bytes, Some(1024));
+        assert_eq!(stats.network_tx_bytes, Some(206));
+        assert_eq!(stats.block_read_bytes, Some(5120));
+        assert_eq!(stats.block_write_bytes, Some(8192));
+        assert_eq!(stats.collected_at.unwrap().seconds, 1_714_566_600);
+    }
+
+    #[test]
+    fn container_stats_without_previous_sample() {
+        let mut response: ContainerStatsResponse = serde_json::from_str(STATS).unwrap();
+        response.precpu_stats = None;
+
+        assert_eq!(ContainerStats::from(&response).cpu_percent, None);
+    }
+
+    #[test]
+    fn exited_state_sets_exit_code_and_finished_at() {
+        let mut status = ContainerStatus::default();
+        status.apply_inspected_state(&bollard::secret::ContainerState {
+            status: Some(ContainerStateStatusEnum::EXITED),
+            exit_code: Some(137),
+            finished_at: Some("2024-05-01T12:30:00.5Z".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(status.container_state(), ContainerState::Exited);
+        assert_eq!(status.exit_code, Some(137));
+        let finished_at = status.finished_at.unwrap();
+        assert_eq!(
+            (finished_at.seconds, finished_at.nanos),
+            (1_714_566_600, 500_000_000)
+        );
+    }
+
+    #[test]
+    fn running_state_has_no_exit_code() {
+        let mut status = ContainerStatus::default();
+        status.apply_inspected_state(&bollard::secret::ContainerState {
+            status: Some(ContainerStateStatusEnum::RUNNING),
+            exit_code: Some(0),
+            finished_at: Some("0001-01-01T00:00:00Z".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(status.container_state(), ContainerState::Running);
+        assert_eq!(status.exit_code, None);
+        assert_eq!(status.finished_at, None);
+    }
+
+    #[test]
+    fn secret_values_are_redacted_from_debug_output() {
+        let params = RunParams {
+            image_name: Some("nginx".into()),
+            secrets: HashMap::from([(
+                "DATABASE_PASSWORD".to_string(),
+                SecretValue {
+                    value: Some("hunter2".into()),
+                },
+            )]),
+            ..Default::default()
+        };
+
+        let debug = format!("{params:?}");
+        assert!(debug.contains("DATABASE_PASSWORD"));
+        assert!(!debug.contains("hunter2"));
+    }
+}
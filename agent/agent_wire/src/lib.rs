@@ -2,6 +2,9 @@ pub mod impls;
 
 pub mod deploything {
     pub mod v1 {
+        // Generated message sizes follow the protos, so oneofs can have lopsided variants.
+        #![allow(clippy::large_enum_variant)]
+
         include!(concat!(env!("OUT_DIR"), "/deploything.v1.rs"));
     }
 }